@@ -46,6 +46,9 @@ impl NonFungibleTokenCore for Contract {
         //get the token object from the token ID
         let mut token = self.token_by_id.get(&token_id).expect("No token found");
 
+        //soulbound tokens can't be transferred so nobody can be approved to transfer them
+        assert!(!token.soulbound, "Soulbound tokens can't be approved");
+
         //make sure that the person calling the function is the owner of the token
         assert_eq!(
             &env::predecessor_account_id(),
@@ -0,0 +1,77 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+#[near_bindgen]
+impl Contract {
+    //burns a token. The owner or an approved account can burn it and the freed storage is refunded to the owner.
+    //the contract owner can also burn soulbound tokens to revoke them from the account they were issued to
+    #[payable]
+    pub fn nft_burn(&mut self, token_id: TokenId, approval_id: Option<u64>, memo: Option<String>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        // measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //get the token object from the token ID
+        let token = self.token_by_id.get(&token_id).expect("No token found");
+
+        //if the caller isn't the owner or the issuer revoking a soulbound token, they need to be approved for the token
+        let sender_id = env::predecessor_account_id();
+        let is_issuer_revocation = token.soulbound && sender_id == self.owner_id;
+        if sender_id != token.owner_id && !is_issuer_revocation {
+            let actual_approval_id = token.approved_account_ids.get(&sender_id).expect("Unauthorized");
+
+            // If they included an approval_id, make sure it's the same as the sender's actual approval_id
+            if let Some(enforced_approval_id) = approval_id {
+                assert_eq!(
+                    actual_approval_id,
+                    &enforced_approval_id,
+                    "The actual approval id {} is different from the given approval_id {}",
+                    actual_approval_id,
+                    enforced_approval_id
+                );
+            }
+        }
+
+        //remove the token, its metadata and the token from the owner's set
+        self.token_by_id.remove(&token_id);
+        self.token_metadata_by_id.remove(&token_id);
+        self.internal_remove_token_to_owner(&token.owner_id, &token_id);
+        self.reference_hash_history.remove(&token_id);
+
+        // Default the authorized ID to be None for the logs
+        let mut authorized_id = None;
+        //if the token was burned by an approved account, set the authorized ID equal to the sender
+        if sender_id != token.owner_id {
+            authorized_id = Some(sender_id.to_string());
+        }
+
+        // Construct the burn log as per the events standard.
+        let nft_burn_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // Vector of token IDs that were burned.
+                token_ids: vec![token_id],
+                // The optional approved account that burned the token on behalf of the owner.
+                authorized_id,
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_burn_log.to_string());
+
+        //calculate the storage released by the token. This includes the storage used by the approved account IDs
+        let released_storage_in_bytes = initial_storage_usage - env::storage_usage();
+
+        //refund the released storage to the owner of the token
+        Promise::new(token.owner_id).transfer(Balance::from(released_storage_in_bytes) * env::storage_byte_cost());
+    }
+}
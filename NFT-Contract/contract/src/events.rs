@@ -3,7 +3,7 @@ use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json;
 
 // Enum that represents the data type of the EventLog
-// The enum can either be an NftMint or an NftTransfer
+// The enum can either be an NftMint, an NftTransfer, an NftBurn or an NftMetadataUpdate
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -11,7 +11,9 @@ use near_sdk::serde_json;
 #[non_exhaustive]
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
-    NftTransfer(Vec<NftTransferLog>)
+    NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+    NftMetadataUpdate(Vec<NftMetadataUpdateLog>)
 }
 
 /// Interface to capture data about an event
@@ -75,4 +77,38 @@ pub struct NftTransferLog {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memo: Option<String>
-}
\ No newline at end of file
+}
+
+/// An event log to capture token burning
+///
+/// Arguments
+/// * `owner_id`: owner of the burned tokens
+/// * `token_ids`: ["1", "12345abc"]
+/// * `authorized_id`: approved account that burned the tokens
+/// * `memo`: optional message
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>
+}
+
+/// An event log to capture token metadata updates
+///
+/// Arguments
+/// * `token_ids`: ["1", "12345abc"]
+/// * `memo`: optional message
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataUpdateLog {
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>
+}
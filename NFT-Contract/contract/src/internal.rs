@@ -48,6 +48,22 @@ pub(crate) fn refund_deposit(storage_used: u64) {
     }
 }
 
+//charge the caller for the storage used since the initial storage usage or refund them the storage that was released.
+//released storage always goes to the predecessor (the account updating the metadata), even if another account such as
+//whoever minted the token paid for that storage in the first place
+pub(crate) fn refund_storage_delta(initial_storage_usage: u64, final_storage_usage: u64) {
+    if final_storage_usage > initial_storage_usage {
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(final_storage_usage - initial_storage_usage);
+    } else {
+        //refund the released storage along with the attached deposit
+        let refund = Balance::from(initial_storage_usage - final_storage_usage) * env::storage_byte_cost() + env::attached_deposit();
+        if refund > 1 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+}
+
 //refund the storage taken up by passed in approved account IDs and send the funds to the passed in account ID. 
 //the approved account IDs must be passed in as an iterator
 pub(crate) fn refund_approved_account_ids_iter<'a, I>(account_id: AccountId, approved_account_ids: I) -> Promise 
@@ -109,6 +125,9 @@ impl Contract {
         // get the token object by passing in the token_id
         let token = self.token_by_id.get(token_id).expect("No token found");
 
+        // soulbound tokens stay with the account they were minted to
+        assert!(!token.soulbound, "Soulbound tokens can't be transferred");
+
         // if the sender doesn't equal the owner, we panic
         if sender_id != &token.owner_id {
             if !token.approved_account_ids.contains_key(sender_id) {
@@ -140,7 +159,7 @@ impl Contract {
         self.internal_add_token_to_owner(receiver_id, token_id);
 
         // we create a new token struct
-        let new_token = Token { owner_id: receiver_id.clone(), approved_account_ids: Default::default(), next_approval_id: token.next_approval_id, royalty: token.royalty.clone() };
+        let new_token = Token { owner_id: receiver_id.clone(), approved_account_ids: Default::default(), next_approval_id: token.next_approval_id, royalty: token.royalty.clone(), soulbound: token.soulbound };
 
         //insert that new token into the tokens_by_id, replacing the old entry 
         self.token_by_id.insert(token_id, &new_token);
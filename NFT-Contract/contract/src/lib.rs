@@ -8,20 +8,25 @@ use near_sdk::{
 use std::collections::HashMap;
 
 mod approval;
+mod burn;
 mod enumeration;
 mod metadata;
+mod metadata_update;
 mod mint;
 mod nft_core;
 mod royalty;
+mod roles;
 mod internal;
 mod events;
 
 pub use crate::approval::*;
 pub use crate::enumeration::*;
 pub use crate::metadata::*;
+pub use crate::metadata_update::*;
 pub use crate::mint::*;
 pub use crate::nft_core::*;
 pub use crate::royalty::*;
+pub use crate::roles::*;
 use crate::internal::*;
 pub use crate::events::*;
 
@@ -39,6 +44,16 @@ pub struct Contract {
     pub tokens_by_owner: LookupMap<AccountId, UnorderedSet<TokenId>>, // Mapping owner => ds tokenId
     pub token_by_id: LookupMap<TokenId, Token>,                       // Mapping tokenId => token
     pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>, // Mapping tokenId => token metadata
+
+    pub admins: UnorderedSet<AccountId>,                              // Accounts that can manage minters and the mint settings
+    pub minters: UnorderedSet<AccountId>,                             // Accounts that can always mint
+    pub public_mint_enabled: bool,                                    // If true, accounts without a role can mint
+    pub public_mint_cap: Option<u64>,                                 // Most tokens an account without a role can mint
+    pub public_mints_by_account: LookupMap<AccountId, u64>,           // Mapping accountId => tokens minted in public mint
+    pub max_supply: Option<u64>,                                      // Most tokens that can ever be minted
+    pub minted_count: u64,                                            // Tokens ever minted. Burning a token doesn't lower it
+
+    pub reference_hash_history: LookupMap<TokenId, Vec<Base64VecU8>>, // Mapping tokenId => previous reference hashes
 }
 
 // Helper structure for keys of the persistent collections
@@ -52,6 +67,10 @@ pub enum StorageKey {
     TokensPerType,
     TokensPerTypeInner { token_type_hash: CryptoHash },
     TokenTypesLocked,
+    Admins,
+    Minters,
+    PublicMintsByAccount,
+    ReferenceHashHistory,
 }
 
 #[near_bindgen]
@@ -71,6 +90,14 @@ impl Contract {
             token_metadata_by_id: UnorderedMap::new(
                 StorageKey::TokenMetadataById.try_to_vec().unwrap(),
             ),
+            admins: UnorderedSet::new(StorageKey::Admins.try_to_vec().unwrap()),
+            minters: UnorderedSet::new(StorageKey::Minters.try_to_vec().unwrap()),
+            public_mint_enabled: false,
+            public_mint_cap: None,
+            public_mints_by_account: LookupMap::new(StorageKey::PublicMintsByAccount.try_to_vec().unwrap()),
+            max_supply: None,
+            minted_count: 0,
+            reference_hash_history: LookupMap::new(StorageKey::ReferenceHashHistory.try_to_vec().unwrap()),
         }
     }
 
@@ -91,3 +118,411 @@ impl Contract {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::NonFungibleTokenCore as NonFungibleTokenApproval;
+    use crate::nft_core::NonFungibleTokenCore;
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::mock::VmAction;
+    use near_sdk::testing_env;
+
+    const OWNER: &str = "owner.near";
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+    const NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+    fn set_context(predecessor: &str, deposit: Balance) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("nft.near".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .signer_account_id(predecessor.parse().unwrap())
+            .attached_deposit(deposit)
+            .build());
+    }
+
+    fn metadata(title: &str) -> TokenMetadata {
+        TokenMetadata {
+            title: Some(title.to_string()),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    //the contract owner mints a token to the receiver
+    fn mint(contract: &mut Contract, token_id: &str, receiver_id: &str) {
+        set_context(OWNER, NEAR);
+        contract.nft_mint(token_id.to_string(), metadata(token_id), receiver_id.parse().unwrap(), None, None);
+    }
+
+    //the NEAR transfers the last call made, as (receiver, amount)
+    fn transfers() -> Vec<(AccountId, Balance)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn burn_removes_token_and_refunds_owner() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+        mint(&mut contract, "2", ALICE);
+
+        set_context(ALICE, 1);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_burn("1".to_string(), None, None);
+        let released_storage = Balance::from(initial_storage_usage - env::storage_usage());
+
+        assert!(contract.nft_token("1".to_string()).is_none());
+        assert!(contract.token_metadata_by_id.get(&"1".to_string()).is_none());
+        assert_eq!(contract.nft_supply_for_owner(ALICE.parse().unwrap()), U128(1));
+        //the freed storage goes back to the owner
+        assert_eq!(transfers(), vec![(ALICE.parse().unwrap(), released_storage * env::storage_byte_cost())]);
+
+        //burning the owner's last token drops their token set
+        contract.nft_burn("2".to_string(), None, None);
+        assert!(contract.tokens_by_owner.get(&ALICE.parse().unwrap()).is_none());
+        assert_eq!(contract.token_metadata_by_id.len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn burn_rejects_unapproved_caller() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+
+        set_context(BOB, 1);
+        contract.nft_burn("1".to_string(), None, None);
+    }
+
+    #[test]
+    fn approved_burn_logs_nep171_event() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+        set_context(ALICE, NEAR);
+        contract.nft_approve("1".to_string(), BOB.parse().unwrap(), None);
+
+        set_context(BOB, 1);
+        contract.nft_burn("1".to_string(), Some(0), Some("burned".to_string()));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_burn","data":[{"owner_id":"alice.near","token_ids":["1"],"authorized_id":"bob.near","memo":"burned"}]}"#]
+        );
+    }
+
+    fn mint_args(token_id: &str, receiver_id: &str) -> TokenMintArgs {
+        TokenMintArgs {
+            token_id: token_id.to_string(),
+            metadata: metadata(token_id),
+            receiver_id: receiver_id.parse().unwrap(),
+            perpetual_royalties: None,
+            soulbound: None,
+        }
+    }
+
+    #[test]
+    fn batch_mint_groups_event_by_owner_and_refunds_once() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+
+        set_context(OWNER, NEAR);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_batch_mint(vec![mint_args("1", ALICE), mint_args("2", BOB), mint_args("3", ALICE)]);
+        let used_storage = Balance::from(env::storage_usage() - initial_storage_usage);
+
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"alice.near","token_ids":["1","3"]},{"owner_id":"bob.near","token_ids":["2"]}]}"#]
+        );
+        //the storage of all the tokens is charged together and the rest of the deposit comes back in a single refund
+        assert_eq!(transfers(), vec![(OWNER.parse().unwrap(), NEAR - used_storage * env::storage_byte_cost())]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token already exists")]
+    fn batch_mint_with_duplicate_token_reverts() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+
+        //the second token reuses the ID of the first so the whole call panics and nothing is minted
+        set_context(OWNER, NEAR);
+        contract.nft_batch_mint(vec![mint_args("1", ALICE), mint_args("1", BOB)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot mint more than 50 tokens at once")]
+    fn batch_mint_is_capped() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+
+        set_context(OWNER, NEAR);
+        contract.nft_batch_mint((0..=MAX_BATCH_MINT).map(|i| mint_args(&i.to_string(), ALICE)).collect());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not approved minter")]
+    fn mint_rejects_account_without_role() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+
+        set_context(BOB, NEAR);
+        contract.nft_mint("1".to_string(), metadata("1"), BOB.parse().unwrap(), None, None);
+    }
+
+    #[test]
+    fn minter_role_can_mint() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.grant_role(Role::Minter, BOB.parse().unwrap());
+
+        set_context(BOB, NEAR);
+        contract.nft_mint("1".to_string(), metadata("1"), ALICE.parse().unwrap(), None, None);
+        assert_eq!(contract.nft_supply_for_owner(ALICE.parse().unwrap()), U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't mint more than 2 tokens in public mint")]
+    fn public_mint_cap_counts_single_and_batch_mints() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.set_public_mint(true, Some(2));
+
+        set_context(BOB, NEAR);
+        contract.nft_mint("1".to_string(), metadata("1"), BOB.parse().unwrap(), None, None);
+        assert_eq!(contract.get_public_mints(BOB.parse().unwrap()), 1);
+        //one more token would fit the cap but a batch of two doesn't
+        contract.nft_batch_mint(vec![mint_args("2", BOB), mint_args("3", BOB)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Max supply of 2 tokens reached")]
+    fn max_supply_rejects_batch_crossing_it() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.set_max_supply(Some(2));
+        mint(&mut contract, "1", ALICE);
+
+        set_context(OWNER, NEAR);
+        contract.nft_batch_mint(vec![mint_args("2", ALICE), mint_args("3", ALICE)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Max supply of 1 tokens reached")]
+    fn burning_does_not_free_max_supply() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.set_max_supply(Some(1));
+        mint(&mut contract, "1", ALICE);
+        set_context(ALICE, 1);
+        contract.nft_burn("1".to_string(), None, None);
+        assert_eq!(contract.get_mint_config().minted_count, 1);
+
+        mint(&mut contract, "2", ALICE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner or an admin can call this method")]
+    fn only_owner_or_admin_can_grant_minter() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+
+        set_context(BOB, 0);
+        contract.grant_role(Role::Minter, BOB.parse().unwrap());
+    }
+
+    #[test]
+    fn admin_can_manage_minters() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.grant_role(Role::Admin, ALICE.parse().unwrap());
+
+        set_context(ALICE, 0);
+        contract.grant_role(Role::Minter, BOB.parse().unwrap());
+        assert!(contract.has_role(Role::Minter, BOB.parse().unwrap()));
+        contract.revoke_role(Role::Minter, BOB.parse().unwrap());
+        assert!(!contract.has_role(Role::Minter, BOB.parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can manage admins")]
+    fn admin_cannot_grant_admin() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.grant_role(Role::Admin, ALICE.parse().unwrap());
+
+        set_context(ALICE, 0);
+        contract.grant_role(Role::Admin, BOB.parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "The owner role can't be granted or revoked")]
+    fn admin_cannot_grant_owner() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.grant_role(Role::Admin, ALICE.parse().unwrap());
+
+        set_context(ALICE, 0);
+        contract.grant_role(Role::Owner, ALICE.parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "The owner role can't be granted or revoked")]
+    fn admin_cannot_revoke_owner() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, 0);
+        contract.grant_role(Role::Admin, ALICE.parse().unwrap());
+
+        set_context(ALICE, 0);
+        contract.revoke_role(Role::Owner, OWNER.parse().unwrap());
+    }
+
+    //the contract owner mints a soulbound token to alice
+    fn mint_soulbound(contract: &mut Contract, token_id: &str) {
+        set_context(OWNER, NEAR);
+        contract.nft_mint(token_id.to_string(), metadata(token_id), ALICE.parse().unwrap(), None, Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "Soulbound tokens can't be transferred")]
+    fn soulbound_token_cannot_be_transferred() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint_soulbound(&mut contract, "1");
+
+        set_context(ALICE, 1);
+        contract.nft_transfer(BOB.parse().unwrap(), "1".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Soulbound tokens can't be approved")]
+    fn soulbound_token_cannot_be_approved() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint_soulbound(&mut contract, "1");
+
+        set_context(ALICE, NEAR);
+        contract.nft_approve("1".to_string(), BOB.parse().unwrap(), None);
+    }
+
+    #[test]
+    fn soulbound_token_can_be_burned_or_revoked() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint_soulbound(&mut contract, "1");
+        mint_soulbound(&mut contract, "2");
+        assert!(contract.nft_token("1".to_string()).unwrap().soulbound);
+
+        //the holder can burn their own token
+        set_context(ALICE, 1);
+        contract.nft_burn("1".to_string(), None, None);
+        assert!(contract.nft_token("1".to_string()).is_none());
+
+        //the contract owner can revoke the token they issued
+        set_context(OWNER, 1);
+        contract.nft_burn("2".to_string(), None, None);
+        assert!(contract.nft_token("2".to_string()).is_none());
+        assert!(contract.tokens_by_owner.get(&ALICE.parse().unwrap()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn contract_owner_cannot_burn_transferable_token() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+
+        set_context(OWNER, 1);
+        contract.nft_burn("1".to_string(), None, None);
+    }
+
+    fn metadata_update() -> TokenMetadataUpdate {
+        TokenMetadataUpdate {
+            title: None,
+            description: None,
+            media: None,
+            media_hash: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    #[test]
+    fn growing_metadata_charges_the_caller() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+
+        set_context(OWNER, NEAR);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_update_metadata("1".to_string(), TokenMetadataUpdate { description: Some("x".repeat(500)), ..metadata_update() }, None);
+        let used_storage = Balance::from(env::storage_usage() - initial_storage_usage);
+
+        //the extra storage is taken from the deposit and the rest is refunded
+        assert!(used_storage >= 500);
+        assert_eq!(transfers(), vec![(OWNER.parse().unwrap(), NEAR - used_storage * env::storage_byte_cost())]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn growing_metadata_requires_storage_deposit() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+
+        set_context(OWNER, 1);
+        contract.nft_update_metadata("1".to_string(), TokenMetadataUpdate { description: Some("x".repeat(500)), ..metadata_update() }, None);
+    }
+
+    #[test]
+    fn shrinking_metadata_refunds_the_caller() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+        set_context(OWNER, NEAR);
+        contract.nft_update_metadata("1".to_string(), TokenMetadataUpdate { description: Some("x".repeat(500)), ..metadata_update() }, None);
+
+        set_context(OWNER, 1);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_update_metadata("1".to_string(), TokenMetadataUpdate { description: Some("x".to_string()), ..metadata_update() }, None);
+        let released_storage = Balance::from(initial_storage_usage - env::storage_usage());
+
+        //the released storage goes to the caller along with their deposit, not to the token owner
+        assert!(released_storage >= 499);
+        assert_eq!(transfers(), vec![(OWNER.parse().unwrap(), released_storage * env::storage_byte_cost() + 1)]);
+    }
+
+    #[test]
+    fn reference_hash_history_drops_oldest_hash() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+
+        //every update replaces the reference hash so the previous one goes into the history
+        set_context(OWNER, NEAR);
+        for i in 0..=MAX_REFERENCE_HASH_HISTORY as u8 + 1 {
+            contract.nft_update_metadata("1".to_string(), TokenMetadataUpdate { reference_hash: Some(Base64VecU8(vec![i])), ..metadata_update() }, None);
+        }
+
+        //the first hash didn't replace anything and the next one was dropped once the history was full
+        let history = contract.nft_reference_hash_history("1".to_string());
+        assert_eq!(history.len(), MAX_REFERENCE_HASH_HISTORY);
+        assert_eq!(history[0].0, vec![1]);
+        assert_eq!(history[MAX_REFERENCE_HASH_HISTORY - 1].0, vec![MAX_REFERENCE_HASH_HISTORY as u8]);
+        assert_eq!(contract.token_metadata_by_id.get(&"1".to_string()).unwrap().reference_hash.unwrap().0, vec![MAX_REFERENCE_HASH_HISTORY as u8 + 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can update metadata")]
+    fn only_contract_owner_can_update_metadata() {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        mint(&mut contract, "1", ALICE);
+
+        set_context(ALICE, NEAR);
+        contract.nft_update_metadata("1".to_string(), metadata_update(), None);
+    }
+}
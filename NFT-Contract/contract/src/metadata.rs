@@ -41,7 +41,8 @@ pub struct Token {
     pub owner_id: AccountId,                            // owner of the token
     pub approved_account_ids: HashMap<AccountId, u64>,  // list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
     pub next_approval_id: u64,                          //the next approval ID to give out. 
-    pub royalty: HashMap<AccountId, u32>
+    pub royalty: HashMap<AccountId, u32>,
+    pub soulbound: bool                                 // if true, the token can't be transferred or approved. It can still be burned
 }
 
 //The Json token is what will be returned from view calls. 
@@ -52,7 +53,8 @@ pub struct JsonToken {
     pub owner_id: AccountId,                            // owner of the token
     pub metadata: TokenMetadata,                        // token metadata
     pub approved_account_ids: HashMap<AccountId, u64>,  //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
-    pub royalty: HashMap<AccountId, u32>
+    pub royalty: HashMap<AccountId, u32>,
+    pub soulbound: bool                                 // whether the token is non-transferable
 }
 
 pub trait NonFungibleTokenMetadata {
@@ -1,40 +1,33 @@
 use crate::*;
+use near_sdk::require;
+use std::collections::BTreeMap;
+
+//the most tokens that can be minted in one nft_batch_mint call so the batch fits in the gas limit and the mint event stays small
+pub const MAX_BATCH_MINT: usize = 50;
+
+//the arguments for a single token minted as part of a batch
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMintArgs {
+    pub token_id: TokenId,
+    pub metadata: TokenMetadata,
+    pub receiver_id: AccountId,
+    pub perpetual_royalties: Option<HashMap<AccountId, u32>>,
+    pub soulbound: Option<bool>,
+}
 
 #[near_bindgen]
 impl Contract {
     #[payable]
-    pub fn nft_mint(&mut self, token_id: TokenId, metadata: TokenMetadata, receiver_id: AccountId, perpetual_royalties: Option<HashMap<AccountId, u32>>) {
+    pub fn nft_mint(&mut self, token_id: TokenId, metadata: TokenMetadata, receiver_id: AccountId, perpetual_royalties: Option<HashMap<AccountId, u32>>, soulbound: Option<bool>) {
         // measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
-        // create a royalty map to store in the token
-        let mut royalty = HashMap::new();
-
-        // if perpetual royalties were passed into the function: 
-        if let Some(perpetual_royalties) = perpetual_royalties {
-            //make sure that the length of the perpetual royalties is below 7 since we won't have enough GAS to pay out that many people
-            assert!(perpetual_royalties.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
-
-            //iterate through the perpetual royalties and insert the account and amount in the royalty map
-            for (account, amount) in perpetual_royalties {
-                royalty.insert(account, amount);
-            }
-        };
-
-        //specify the token struct that contains the owner ID 
-        let token = Token { owner_id: receiver_id, approved_account_ids: Default::default(), next_approval_id: 0, royalty };
-
-        //insert the token ID and token struct and make sure that the token doesn't exist
-        assert!(
-            self.token_by_id.insert(&token_id, &token).is_none(), 
-            "Token already exists"
-        );
+        //make sure the caller is allowed to mint and the token fits in the max supply
+        self.internal_assert_can_mint(1);
 
-        //insert the token ID and metadata
-        self.token_metadata_by_id.insert(&token_id, &metadata);
-
-        //call the internal method for adding the token to the owner
-        self.internal_add_token_to_owner(&token.owner_id, &token_id);
+        //mint the token and add it to the receiver
+        self.internal_mint(&token_id, metadata, &receiver_id, perpetual_royalties, soulbound.unwrap_or(false));
 
         // Construct the mint log as per the events standard.
         let nft_mint_log: EventLog = EventLog { 
@@ -45,7 +38,7 @@ impl Contract {
             // The data related with the event stored in a vector.
             event: EventLogVariant::NftMint(vec![NftMintLog {
                 // Owner of the token.
-                owner_id: token.owner_id.to_string(),
+                owner_id: receiver_id.to_string(),
                 // Vector of token IDs that were minted.
                 token_ids: vec![token_id.to_string()],
                 // An optional memo to include.
@@ -62,4 +55,100 @@ impl Contract {
         //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
         refund_deposit(required_storage_in_bytes);
     }
-}
\ No newline at end of file
+
+    /*
+        mints several tokens in a single call. The caller pays for the storage of all the tokens at once and a single
+        mint event is emitted with the token IDs grouped by owner. If any token ID already exists, nothing is minted.
+    */
+    #[payable]
+    pub fn nft_batch_mint(&mut self, tokens: Vec<TokenMintArgs>) {
+        require!(!tokens.is_empty(), "Must mint at least 1 token");
+        require!(tokens.len() <= MAX_BATCH_MINT, format!("Cannot mint more than {} tokens at once", MAX_BATCH_MINT));
+        // measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //make sure the caller is allowed to mint and all the tokens fit in the max supply
+        self.internal_assert_can_mint(tokens.len() as u64);
+
+        //mint every token and keep track of the token IDs minted for every owner
+        let mut token_ids_by_owner: BTreeMap<AccountId, Vec<String>> = BTreeMap::new();
+        for TokenMintArgs { token_id, metadata, receiver_id, perpetual_royalties, soulbound } in tokens {
+            //if the token already exists, this panics and the whole batch is reverted
+            self.internal_mint(&token_id, metadata, &receiver_id, perpetual_royalties, soulbound.unwrap_or(false));
+            token_ids_by_owner.entry(receiver_id).or_default().push(token_id);
+        }
+
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector. There is one entry for every owner.
+            event: EventLogVariant::NftMint(
+                token_ids_by_owner
+                    .into_iter()
+                    .map(|(owner_id, token_ids)| NftMintLog {
+                        // Owner of the tokens.
+                        owner_id: owner_id.to_string(),
+                        // Vector of token IDs that were minted to the owner.
+                        token_ids,
+                        // An optional memo to include.
+                        memo: None,
+                    })
+                    .collect(),
+            ),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+
+        //calculate the storage used by all the tokens together
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(required_storage_in_bytes);
+    }
+}
+
+impl Contract {
+    //internal method for minting a token to the receiver. Panics if the token already exists
+    pub(crate) fn internal_mint(
+        &mut self,
+        token_id: &TokenId,
+        metadata: TokenMetadata,
+        receiver_id: &AccountId,
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        soulbound: bool,
+    ) {
+        // create a royalty map to store in the token
+        let mut royalty = HashMap::new();
+
+        // if perpetual royalties were passed into the function: 
+        if let Some(perpetual_royalties) = perpetual_royalties {
+            //make sure that the length of the perpetual royalties is below 7 since we won't have enough GAS to pay out that many people
+            assert!(perpetual_royalties.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
+
+            //iterate through the perpetual royalties and insert the account and amount in the royalty map
+            for (account, amount) in perpetual_royalties {
+                royalty.insert(account, amount);
+            }
+        };
+
+        //specify the token struct that contains the owner ID 
+        let token = Token { owner_id: receiver_id.clone(), approved_account_ids: Default::default(), next_approval_id: 0, royalty, soulbound };
+
+        //insert the token ID and token struct and make sure that the token doesn't exist
+        assert!(
+            self.token_by_id.insert(token_id, &token).is_none(), 
+            "Token already exists"
+        );
+
+        //insert the token ID and metadata
+        self.token_metadata_by_id.insert(token_id, &metadata);
+
+        //call the internal method for adding the token to the owner
+        self.internal_add_token_to_owner(&token.owner_id, token_id);
+        self.minted_count += 1;
+    }
+}
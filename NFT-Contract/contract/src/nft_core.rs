@@ -97,6 +97,7 @@ impl NonFungibleTokenCore for Contract {
                 metadata,
                 approved_account_ids: token.approved_account_ids,
                 royalty: token.royalty,
+                soulbound: token.soulbound,
             })
         } else { //if there wasn't a token ID in the tokens_by_id collection, we return None
             None
@@ -0,0 +1,140 @@
+use crate::*;
+use near_sdk::require;
+
+/// The roles an account can have on the contract. The owner can manage admins, the owner and admins can manage minters and
+/// the mint settings, and the owner, admins and minters can always mint.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Admin,
+    Minter,
+}
+
+/// The mint settings of the contract
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintConfig {
+    // If true, anyone can mint and not only accounts with a role
+    pub public_mint_enabled: bool,
+    // The most tokens an account without a role can mint in public mint mode. If None, there is no limit
+    pub public_mint_cap: Option<u64>,
+    // The most tokens that can ever be minted on the contract. Burned tokens still count towards it. If None, there is no limit
+    pub max_supply: Option<u64>,
+    // The number of tokens ever minted on the contract, including the ones that were burned since
+    pub minted_count: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Grant a role to an account. Only the owner can grant the admin role and only the owner or an admin can grant the minter role
+    pub fn grant_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_can_manage_role(role);
+        match role {
+            Role::Admin => self.admins.insert(&account_id),
+            Role::Minter => self.minters.insert(&account_id),
+            Role::Owner => unreachable!(),
+        };
+    }
+
+    /// Revoke a role from an account. The same accounts that can grant a role can revoke it
+    pub fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_can_manage_role(role);
+        match role {
+            Role::Admin => self.admins.remove(&account_id),
+            Role::Minter => self.minters.remove(&account_id),
+            Role::Owner => unreachable!(),
+        };
+    }
+
+    /// Turn public minting on or off and set how many tokens every account without a role can mint. Only the owner or an admin can call this
+    pub fn set_public_mint(&mut self, enabled: bool, per_account_cap: Option<u64>) {
+        self.assert_owner_or_admin();
+        self.public_mint_enabled = enabled;
+        self.public_mint_cap = per_account_cap;
+    }
+
+    /// Set the most tokens that can ever be minted on the contract. Burning tokens doesn't free up room. Only the owner or an admin can call this
+    pub fn set_max_supply(&mut self, max_supply: Option<u64>) {
+        self.assert_owner_or_admin();
+        if let Some(max_supply) = max_supply {
+            assert!(max_supply >= self.minted_count, "Max supply can't be lower than the number of tokens already minted");
+        }
+        self.max_supply = max_supply;
+    }
+
+    /// Check if a specified account has a role
+    pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+        match role {
+            Role::Owner => account_id == self.owner_id,
+            Role::Admin => self.admins.contains(&account_id),
+            Role::Minter => self.minters.contains(&account_id),
+        }
+    }
+
+    /// Get the accounts that have a role
+    pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+        match role {
+            Role::Owner => vec![self.owner_id.clone()],
+            Role::Admin => self.admins.to_vec(),
+            Role::Minter => self.minters.to_vec(),
+        }
+    }
+
+    /// Get the mint settings of the contract
+    pub fn get_mint_config(&self) -> MintConfig {
+        MintConfig {
+            public_mint_enabled: self.public_mint_enabled,
+            public_mint_cap: self.public_mint_cap,
+            max_supply: self.max_supply,
+            minted_count: self.minted_count,
+        }
+    }
+
+    /// Get how many tokens an account has minted in public mint mode
+    pub fn get_public_mints(&self, account_id: AccountId) -> u64 {
+        self.public_mints_by_account.get(&account_id).unwrap_or(0)
+    }
+}
+
+impl Contract {
+    // make sure the caller is the owner or an admin
+    pub(crate) fn assert_owner_or_admin(&self) {
+        let predecessor = env::predecessor_account_id();
+        assert!(
+            predecessor == self.owner_id || self.admins.contains(&predecessor),
+            "Only the owner or an admin can call this method"
+        );
+    }
+
+    // make sure the caller can grant and revoke the given role
+    fn assert_can_manage_role(&self, role: Role) {
+        require!(role != Role::Owner, "The owner role can't be granted or revoked");
+        match role {
+            Role::Owner => unreachable!(),
+            Role::Admin => assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can manage admins"),
+            Role::Minter => self.assert_owner_or_admin(),
+        }
+    }
+
+    // make sure the caller can mint the given number of tokens. Accounts without a role can only mint in public mint mode up to the per account cap
+    pub(crate) fn internal_assert_can_mint(&mut self, count: u64) {
+        let predecessor = env::predecessor_account_id();
+        if predecessor != self.owner_id && !self.admins.contains(&predecessor) && !self.minters.contains(&predecessor) {
+            assert!(self.public_mint_enabled, "Not approved minter");
+
+            // count the tokens against the account's public mint cap
+            let minted = self.get_public_mints(predecessor.clone()) + count;
+            if let Some(public_mint_cap) = self.public_mint_cap {
+                assert!(minted <= public_mint_cap, "Can't mint more than {} tokens in public mint", public_mint_cap);
+            }
+            self.public_mints_by_account.insert(&predecessor, &minted);
+        }
+
+        // make sure the tokens fit in the max supply. Burned tokens are still counted so the supply can't be minted again
+        if let Some(max_supply) = self.max_supply {
+            assert!(self.minted_count + count <= max_supply, "Max supply of {} tokens reached", max_supply);
+        }
+    }
+}
@@ -53,6 +53,9 @@ impl NonFungibleTokenCore for Contract {
             "Predecessor must be the token owner."
         );
 
+        //soulbound tokens can't be transferred so nobody can be approved to transfer them
+        require!(!self.is_series_soulbound(token.series_id), "Soulbound tokens can't be approved");
+
         //get the next approval ID if we need a new approval
         let approval_id: u64 = token.next_approval_id;
 
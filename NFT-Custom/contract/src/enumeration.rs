@@ -13,6 +13,10 @@ pub struct JsonSeries {
     royalty: Option<HashMap<AccountId, u32>>,
     // Owner of the collection
     owner_id: AccountId,
+    // Price of each token in the series (if minting is open to anyone who pays it)
+    price: Option<U128>,
+    // Whether tokens in the series are non-transferable
+    soulbound: bool,
 }
 
 #[near_bindgen]
@@ -122,6 +126,8 @@ impl Contract {
                 metadata: series.metadata,
                 royalty: series.royalty,
                 owner_id: series.owner_id,
+                price: series.price.map(U128),
+                soulbound: series.soulbound,
             })
         } else {
             //if there isn't a series, we'll return None
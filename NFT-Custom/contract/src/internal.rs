@@ -108,7 +108,28 @@ pub(crate) fn refund_deposit(storage_used: u64) {
     }
 }
 
+//charge the caller for the storage used since the initial storage usage or refund them the storage that was released.
+//released storage always goes to the predecessor (the account updating the metadata), even if another account such as
+//whoever minted the token paid for that storage in the first place
+pub(crate) fn refund_storage_delta(initial_storage_usage: u64, final_storage_usage: u64) {
+    if final_storage_usage > initial_storage_usage {
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(final_storage_usage - initial_storage_usage);
+    } else {
+        //refund the released storage along with the attached deposit
+        let refund = Balance::from(initial_storage_usage - final_storage_usage) * env::storage_byte_cost() + env::attached_deposit();
+        if refund > 1 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+}
+
 impl Contract {
+    /// Check if the tokens of a series can't be transferred
+    pub(crate) fn is_series_soulbound(&self, series_id: SeriesId) -> bool {
+        self.series_by_id.get(&series_id).is_some_and(|series| series.soulbound)
+    }
+
     /// Ensure that the caller is the owner of the contract
     pub(crate) fn assert_contract_owner(&mut self) {
         assert!(
@@ -181,6 +202,9 @@ impl Contract {
         //get the token object by passing in the token_id
         let token = self.tokens_by_id.get(token_id).expect("No token");
 
+        //tokens of a soulbound series stay with the account they were minted to
+        require!(!self.is_series_soulbound(token.series_id), "Soulbound tokens can't be transferred");
+
         //if the sender doesn't equal the owner, we check if the sender is in the approval list
         if sender_id != &token.owner_id {
             //if the token's approved account IDs doesn't contain the sender, we panic
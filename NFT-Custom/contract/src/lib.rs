@@ -12,16 +12,19 @@ pub use crate::approval::*;
 pub use crate::events::*;
 use crate::internal::*;
 pub use crate::metadata::*;
+pub use crate::metadata_update::*;
 pub use crate::nft_core::*;
 pub use crate::owner::*;
 pub use crate::royalty::*;
 pub use crate::series::*;
 
 mod approval;
+mod burn;
 mod enumeration;
 mod events;
 mod internal;
 mod metadata;
+mod metadata_update;
 mod nft_core;
 mod owner;
 mod royalty;
@@ -46,6 +49,10 @@ pub struct Series {
     price: Option<Balance>,
     // Owner of the collection
     owner_id: AccountId,
+    // If true, tokens in the series can't be transferred or approved
+    soulbound: bool,
+    // How many tokens were ever minted in the series. Burned tokens still count so editions are never reused
+    minted_count: u64,
 }
 
 pub type SeriesId = u64;
@@ -73,6 +80,9 @@ pub struct Contract {
 
     //keeps track of the metadata for the contract
     pub metadata: LazyOption<NFTContractMetadata>,
+
+    //keeps track of the previous reference hashes for a given series
+    pub reference_hash_history: LookupMap<SeriesId, Vec<Base64VecU8>>,
 }
 
 /// Helper structure for keys of the persistent collections.
@@ -86,6 +96,7 @@ pub enum StorageKey {
     TokenPerOwnerInner { account_id_hash: CryptoHash },
     TokensById,
     NFTContractMetadata,
+    ReferenceHashHistory,
 }
 
 #[near_bindgen]
@@ -143,9 +154,197 @@ impl Contract {
                 StorageKey::NFTContractMetadata.try_to_vec().unwrap(),
                 Some(&metadata),
             ),
+            reference_hash_history: LookupMap::new(StorageKey::ReferenceHashHistory.try_to_vec().unwrap()),
         };
 
         //return the Contract object
         this
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::NonFungibleTokenCore as NonFungibleTokenApproval;
+    use crate::nft_core::NonFungibleTokenCore;
+    use near_sdk::json_types::U64;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const OWNER: &str = "owner.near";
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+    const NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+    fn set_context(predecessor: &str, deposit: Balance) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("nft.near".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .signer_account_id(predecessor.parse().unwrap())
+            .attached_deposit(deposit)
+            .build());
+    }
+
+    fn metadata(title: &str) -> TokenMetadata {
+        TokenMetadata {
+            title: Some(title.to_string()),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    //the contract owner creates series 1 and mints its first token to alice
+    fn setup_series(soulbound: bool) -> Contract {
+        let mut contract = Contract::new_default_meta(OWNER.parse().unwrap());
+        set_context(OWNER, NEAR);
+        contract.create_series(1, metadata("series"), None, None, Some(soulbound));
+        contract.nft_mint(U64(1), ALICE.parse().unwrap());
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Soulbound tokens can't be transferred")]
+    fn soulbound_token_cannot_be_transferred() {
+        let mut contract = setup_series(true);
+
+        set_context(ALICE, 1);
+        contract.nft_transfer(BOB.parse().unwrap(), "1:1".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Soulbound tokens can't be approved")]
+    fn soulbound_token_cannot_be_approved() {
+        let mut contract = setup_series(true);
+
+        set_context(ALICE, NEAR);
+        contract.nft_approve("1:1".to_string(), BOB.parse().unwrap(), None);
+    }
+
+    #[test]
+    fn soulbound_token_can_be_burned_or_revoked() {
+        let mut contract = setup_series(true);
+        set_context(OWNER, NEAR);
+        contract.nft_mint(U64(1), ALICE.parse().unwrap());
+
+        //the holder can burn their own token
+        set_context(ALICE, 1);
+        contract.nft_burn("1:1".to_string(), None, None);
+        assert!(contract.nft_token("1:1".to_string()).is_none());
+
+        //the series owner can revoke the token they issued
+        set_context(OWNER, 1);
+        contract.nft_burn("1:2".to_string(), None, None);
+        assert!(contract.nft_token("1:2".to_string()).is_none());
+        assert!(contract.tokens_per_owner.get(&ALICE.parse().unwrap()).is_none());
+        assert_eq!(contract.nft_supply_for_series(1), U128(0));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_burn","data":[{"owner_id":"alice.near","token_ids":["1:2"],"authorized_id":"owner.near"}]}"#]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn series_owner_cannot_burn_transferable_token() {
+        let mut contract = setup_series(false);
+
+        set_context(OWNER, 1);
+        contract.nft_burn("1:1".to_string(), None, None);
+    }
+
+    #[test]
+    fn burned_editions_are_not_minted_again() {
+        let mut contract = setup_series(false);
+        set_context(ALICE, 1);
+        contract.nft_burn("1:1".to_string(), None, None);
+
+        set_context(OWNER, NEAR);
+        contract.nft_mint(U64(1), BOB.parse().unwrap());
+        assert!(contract.nft_token("1:1".to_string()).is_none());
+        assert_eq!(contract.nft_token("1:2".to_string()).unwrap().owner_id, BOB.parse::<AccountId>().unwrap());
+    }
+
+    fn metadata_update() -> TokenMetadataUpdate {
+        TokenMetadataUpdate {
+            title: None,
+            description: None,
+            media: None,
+            media_hash: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    //the NEAR transfers the last call made, as (receiver, amount)
+    fn transfers() -> Vec<(AccountId, Balance)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    #[should_panic(expected = "only series owner can update metadata")]
+    fn only_series_owner_can_update_metadata() {
+        let mut contract = setup_series(false);
+
+        set_context(ALICE, NEAR);
+        contract.nft_update_metadata(U64(1), metadata_update(), None);
+    }
+
+    #[test]
+    fn metadata_update_logs_series_and_keeps_bounded_history() {
+        let mut contract = setup_series(false);
+
+        //every update replaces the reference hash so the previous one goes into the history
+        set_context(OWNER, NEAR);
+        for i in 0..12u8 {
+            contract.nft_update_metadata(U64(1), TokenMetadataUpdate { reference_hash: Some(Base64VecU8(vec![i])), ..metadata_update() }, None);
+        }
+        //the first hash didn't replace anything and the next one was dropped once the history was full
+        let history = contract.nft_reference_hash_history(U64(1));
+        assert_eq!(history.len(), 10);
+        assert_eq!(history[0].0, vec![1]);
+        assert_eq!(history[9].0, vec![10]);
+
+        set_context(OWNER, NEAR);
+        contract.nft_update_metadata(U64(1), TokenMetadataUpdate { title: Some("level 2".to_string()), ..metadata_update() }, Some("level up".to_string()));
+        assert_eq!(contract.nft_token("1:1".to_string()).unwrap().metadata.title, Some("level 2 - 1".to_string()));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_metadata_update","data":[{"series_id":"1","memo":"level up"}]}"#]
+        );
+    }
+
+    #[test]
+    fn shrinking_metadata_refunds_released_storage() {
+        let mut contract = setup_series(false);
+        set_context(OWNER, NEAR);
+        contract.nft_update_metadata(U64(1), TokenMetadataUpdate { description: Some("x".repeat(500)), ..metadata_update() }, None);
+
+        set_context(OWNER, 1);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_update_metadata(U64(1), TokenMetadataUpdate { description: Some("x".to_string()), ..metadata_update() }, None);
+        let released_storage = Balance::from(initial_storage_usage - env::storage_usage());
+
+        //the caller gets the released storage back along with their deposit
+        assert!(released_storage >= 499);
+        assert_eq!(transfers(), vec![(OWNER.parse().unwrap(), released_storage * env::storage_byte_cost() + 1)]);
+    }
+}
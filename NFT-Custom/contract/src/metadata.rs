@@ -64,6 +64,8 @@ pub struct JsonToken {
     pub approved_account_ids: HashMap<AccountId, u64>,
     //keep track of the royalty percentages for the token in a hash map
     pub royalty: Option<HashMap<AccountId, u32>>,
+    //whether the token is non-transferable
+    pub soulbound: bool,
 }
 
 pub trait NonFungibleTokenMetadata {
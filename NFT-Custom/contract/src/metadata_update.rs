@@ -0,0 +1,101 @@
+use near_sdk::json_types::U64;
+
+use crate::*;
+
+/// The most previous reference hashes kept for every series
+const MAX_REFERENCE_HASH_HISTORY: usize = 10;
+
+/// The metadata fields that can be changed after a series is created. Fields that aren't passed in are left as they are
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadataUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub extra: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Update the metadata of a series, which every token in the series derives from. Only the series owner can call this.
+    /// If the metadata grows, the caller pays for the extra storage and if it shrinks, the released storage is refunded.
+    /// Every replaced reference hash is kept in the series' reference hash history.
+    #[payable]
+    pub fn nft_update_metadata(&mut self, id: U64, update: TokenMetadataUpdate, memo: Option<String>) {
+        // Assert at least one yocto for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        // Ensure the caller owns the series
+        let mut series = self.series_by_id.get(&id.0).expect("Not a series");
+        require!(env::predecessor_account_id() == series.owner_id, "only series owner can update metadata");
+
+        // Keep the previous reference hash if it's being replaced
+        let metadata = &mut series.metadata;
+        if update.reference_hash.is_some() && update.reference_hash != metadata.reference_hash {
+            if let Some(previous_hash) = metadata.reference_hash.take() {
+                let mut history = self.reference_hash_history.get(&id.0).unwrap_or_default();
+                if history.len() >= MAX_REFERENCE_HASH_HISTORY {
+                    history.remove(0);
+                }
+                history.push(previous_hash);
+                self.reference_hash_history.insert(&id.0, &history);
+            }
+        }
+
+        // Replace the fields that were passed in
+        if update.title.is_some() {
+            metadata.title = update.title;
+        }
+        if update.description.is_some() {
+            metadata.description = update.description;
+        }
+        if update.media.is_some() {
+            metadata.media = update.media;
+        }
+        if update.media_hash.is_some() {
+            metadata.media_hash = update.media_hash;
+        }
+        if update.extra.is_some() {
+            metadata.extra = update.extra;
+        }
+        if update.reference.is_some() {
+            metadata.reference = update.reference;
+        }
+        if update.reference_hash.is_some() {
+            metadata.reference_hash = update.reference_hash;
+        }
+        metadata.updated_at = Some(env::block_timestamp_ms());
+        self.series_by_id.insert(&id.0, &series);
+
+        // Construct the metadata update log as per the events standard.
+        let nft_metadata_update_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+                // Series whose tokens all have new metadata.
+                series_id: id,
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_metadata_update_log.to_string());
+
+        // Charge for the extra storage or refund the released storage
+        refund_storage_delta(initial_storage_usage, env::storage_usage());
+    }
+
+    /// Get the previous reference hashes of a series (oldest first)
+    pub fn nft_reference_hash_history(&self, id: U64) -> Vec<Base64VecU8> {
+        self.reference_hash_history.get(&id.0).unwrap_or_default()
+    }
+}
@@ -184,6 +184,7 @@ impl NonFungibleTokenCore for Contract {
                 metadata,
                 approved_account_ids: token.approved_account_ids,
                 royalty: cur_series.royalty,
+                soulbound: cur_series.soulbound,
             })
         } else {
             //if there wasn't a token ID in the tokens_by_id collection, we return None
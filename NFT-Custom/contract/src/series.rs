@@ -15,7 +15,8 @@ impl Contract {
         id: u64,
         metadata: TokenMetadata,
         royalty: Option<HashMap<AccountId, u32>>,
-        price: Option<U128>
+        price: Option<U128>,
+        soulbound: Option<bool>
     ) {
         // Measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
@@ -44,6 +45,8 @@ impl Contract {
                         }),
                         owner_id: caller,
                         price: price.map(|p| p.into()),
+                        soulbound: soulbound.unwrap_or(false),
+                        minted_count: 0,
                     }
                 )
                 .is_none(),
@@ -64,7 +67,7 @@ impl Contract {
         // Measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
-        // Get the series and how many tokens currently exist (edition number = cur_len + 1)
+        // Get the series and how many tokens were minted so far (edition number = minted_count + 1)
         let mut series = self.series_by_id.get(&id.0).expect("Not a series");
         
         // Check if the series has a price per token. If it does, ensure the caller has attached at least that amount
@@ -82,8 +85,8 @@ impl Contract {
             );
         }
 
-        let cur_len = series.tokens.len();
-        // Ensure we haven't overflowed on the number of copies minted
+        let cur_len = series.minted_count;
+        // Ensure we haven't overflowed on the number of copies minted. Burned copies still count
         if let Some(copies) = series.metadata.copies {
             require!(
                 cur_len < copies,
@@ -94,6 +97,7 @@ impl Contract {
         // The token ID is stored internally as `${series_id}:${edition}`
         let token_id = format!("{}:{}", id.0, cur_len + 1);
         series.tokens.insert(&token_id);
+        series.minted_count += 1;
         self.series_by_id.insert(&id.0, &series);
 
         //specify the token struct that contains the owner ID
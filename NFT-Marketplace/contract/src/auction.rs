@@ -0,0 +1,131 @@
+use crate::*;
+
+//the default window (in nanoseconds) at the end of an auction in which a new bid will extend the end time. (10 minutes)
+const DEFAULT_EXTENSION_WINDOW: u64 = 10 * 60 * 1_000_000_000;
+
+//struct for the auction parameters passed in the `msg` of nft_approve when listing a token as an auction
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionArgs {
+    pub starts_at: U64,                             //block timestamp (in nanoseconds) when bidding opens
+    pub ends_at: U64,                               //block timestamp (in nanoseconds) when bidding closes
+    pub reserve_price: U128,                        //the auction only settles if the highest bid is at least this price
+    pub min_bid_increment: U128,                    //minimum amount a new bid must beat the highest bid by
+    pub extension_window: Option<U64>,              //a bid landing this close to the end pushes the end back by the same amount
+}
+
+//the highest bid currently placed on an auction. The amount is held by the marketplace until the bidder is outbid or the auction settles
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bid {
+    pub bidder_id: AccountId,
+    pub amount: U128,
+}
+
+//struct that holds the state of an english auction attached to a sale
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+    pub starts_at: U64,
+    pub ends_at: U64,
+    pub reserve_price: U128,
+    pub min_bid_increment: U128,
+    pub extension_window: U64,
+    pub highest_bid: Option<Bid>,
+}
+
+impl Auction {
+    //create a new auction from the listing args. Panics if the time window is invalid
+    pub(crate) fn new(args: AuctionArgs) -> Self {
+        assert!(args.ends_at.0 > args.starts_at.0, "Auction must end after it starts");
+        assert!(args.ends_at.0 > env::block_timestamp(), "Auction must end in the future");
+
+        Self {
+            starts_at: args.starts_at,
+            ends_at: args.ends_at,
+            reserve_price: args.reserve_price,
+            min_bid_increment: args.min_bid_increment,
+            extension_window: args.extension_window.unwrap_or(U64(DEFAULT_EXTENSION_WINDOW)),
+            highest_bid: None,
+        }
+    }
+
+    //the lowest amount the next bid can be. The first bid must meet the starting price, every bid after must beat the highest bid by the increment
+    pub(crate) fn min_next_bid(&self, starting_price: Balance) -> Balance {
+        match &self.highest_bid {
+            Some(bid) => bid.amount.0 + self.min_bid_increment.0.max(1),
+            None => starting_price,
+        }
+    }
+
+    //returns true if there's a bid that the marketplace is currently holding funds for
+    pub(crate) fn has_bids(&self) -> bool {
+        self.highest_bid.is_some()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        settles an auction once it has ended. Anyone can call this. If the highest bid met the reserve price, the
+        token is purchased through the regular purchase flow. Otherwise the sale is removed and the highest bidder is refunded.
+    */
+    pub fn settle_auction(&mut self, nft_contract_id: AccountId, token_id: String) {
+        //get the unique sale ID (contract + DELIMITER + token ID)
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let auction = sale.auction.expect("Sale is not an auction");
+
+        //make sure bidding has closed
+        assert!(env::block_timestamp() >= auction.ends_at.0, "Auction has not ended");
+
+        match auction.highest_bid {
+            //the reserve was met so we process the purchase for the winning bid
+            Some(bid) if bid.amount.0 >= auction.reserve_price.0 => {
+                self.process_purchase(nft_contract_id, token_id, bid.amount, bid.bidder_id);
+            }
+            //the reserve wasn't met (or nobody bid) so we remove the sale and refund the highest bidder
+            _ => {
+                self.internal_cancel_sale(nft_contract_id, token_id, "reserve_not_met");
+            }
+        }
+    }
+}
+
+impl Contract {
+    //internal method for placing a bid on an auction. The previous highest bidder is refunded and the end time is extended if needed
+    pub(crate) fn internal_place_bid(
+        &mut self,
+        contract_and_token_id: &ContractAndTokenId,
+        mut sale: Sale,
+        bidder_id: AccountId,
+        amount: Balance,
+    ) {
+        let mut auction = sale.auction.take().expect("Sale is not an auction");
+
+        //make sure the auction is currently accepting bids
+        let now = env::block_timestamp();
+        assert!(now >= auction.starts_at.0, "Auction has not started");
+        assert!(now < auction.ends_at.0, "Auction has ended");
+
+        //make sure the bid beats the current highest bid by at least the increment
+        let min_bid = auction.min_next_bid(sale.sale_conditions.0);
+        assert!(amount >= min_bid, "Bid must be greater than or equal to: {:?}", min_bid);
+
+        //replace the highest bid and refund the bidder who was outbid
+        if let Some(outbid) = auction.highest_bid.replace(Bid { bidder_id, amount: U128(amount) }) {
+            Promise::new(outbid.bidder_id).transfer(outbid.amount.0);
+        }
+
+        //if the bid landed in the last moments of the auction, we push the end back to prevent sniping
+        if auction.ends_at.0 - now < auction.extension_window.0 {
+            auction.ends_at = U64(now + auction.extension_window.0);
+        }
+
+        //insert the sale back into the map for the unique sale ID
+        sale.auction = Some(auction);
+        self.sales.insert(contract_and_token_id, &sale);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::*;
+
+//GAS kept aside in offer_many for refunding the unused deposit after the purchases have been scheduled
+const GAS_RESERVED_FOR_OFFER_MANY: Gas = Gas(10_000_000_000_000);
+//GAS used by offer_many itself for every purchase (removing the sale and the fees for the calls that the purchase schedules)
+const GAS_FOR_OFFER_MANY_ITEM: Gas = Gas(25_000_000_000_000);
+//the most GAS that can be attached to a transaction
+const MAX_TRANSACTION_GAS: Gas = Gas(300_000_000_000_000);
+//the GAS needed for a single purchase in offer_many. Only NEAR sales can be bought in a batch so the payout is resolved with the NEAR budget
+pub(crate) const GAS_PER_OFFER_MANY_PURCHASE: u64 = GAS_FOR_OFFER_MANY_ITEM.0
+    + GAS_FOR_NFT_PAYOUT.0
+    + GAS_FOR_RESOLVE_PURCHASE_PAYOUT.0
+    + GAS_FOR_NFT_TRANSFER.0
+    + GAS_FOR_RESOLVE_NEAR_PURCHASE.0;
+//the maximum number of sales that can be bought with offer_many. Every purchase needs its own payout check, transfer and
+//resolve so this is how many purchases fit in a single transaction
+pub(crate) const MAX_OFFER_MANY: usize = ((MAX_TRANSACTION_GAS.0 - GAS_RESERVED_FOR_OFFER_MANY.0) / GAS_PER_OFFER_MANY_PURCHASE) as usize;
+
+// struct for pointing at one of the sales in a batch
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleKey {
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+}
+
+// struct for updating the price of one of the sales in update_prices
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceUpdate {
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub price: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        buy up to MAX_OFFER_MANY (3) fixed price NEAR sales with one deposit. Every purchase needs 85 Tgas: 60 Tgas attached for
+        checking the payout, the transfer and resolving the purchase with the NEAR budget, plus 25 Tgas for taking the sale down
+        and scheduling those calls. That is why only 3 purchases fit in the 300 Tgas of a transaction, with 10 Tgas kept for the refund.
+        Sales are bought in order until the deposit or the attached GAS runs out.
+        Sales that don't exist, can't be bought with an offer or don't fit in the remaining deposit are skipped.
+        Whatever isn't spent is refunded and the unique sale IDs that were purchased are returned.
+    */
+    #[payable]
+    pub fn offer_many(&mut self, purchases: Vec<SaleKey>) -> Vec<ContractAndTokenId> {
+        //get the attached deposit and make sure it's greater than 0
+        let deposit = env::attached_deposit();
+        assert!(deposit > 0, "Attached deposit must be greater than 0");
+        //make sure the marketplace isn't paused
+        self.assert_not_paused();
+        assert!(purchases.len() <= MAX_OFFER_MANY, "Cannot purchase more than {} sales at once", MAX_OFFER_MANY);
+
+        let buyer_id = env::predecessor_account_id();
+
+        let mut remaining = deposit;
+        let mut purchased = vec![];
+        for SaleKey { nft_contract_id, token_id } in purchases {
+            //stop once there isn't enough GAS left to schedule another purchase and refund the buyer
+            let gas_left = env::prepaid_gas().0.saturating_sub(env::used_gas().0);
+            if gas_left < GAS_PER_OFFER_MANY_PURCHASE + GAS_RESERVED_FOR_OFFER_MANY.0 {
+                env::log_str("Not enough GAS attached for the remaining purchases");
+                break;
+            }
+
+            //get the unique sale ID (contract + DELIMITER + token ID)
+            let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+
+            //skip sales that don't exist anymore
+            let sale = if let Some(sale) = self.sales.get(&contract_and_token_id) {
+                sale
+            } else {
+                continue;
+            };
+
+            //only fixed price NEAR sales that aren't the buyer's own, are open for purchase and fit in the remaining deposit can be bought in a batch
+            let price = sale.current_price();
+            if sale.auction.is_some() || sale.swap_for.is_some() || sale.ft_token_id.is_some() || sale.owner_id == buyer_id || price > remaining
+                || !sale.has_started() || sale.is_expired() || !sale.is_reserved_for(&buyer_id)
+            {
+                continue;
+            }
+
+            //process the purchase for exactly the sale price
+            remaining -= price;
+            self.process_purchase(nft_contract_id, token_id, U128(price), buyer_id.clone());
+            purchased.push(contract_and_token_id);
+        }
+
+        //refund the part of the deposit that wasn't used
+        if remaining > 0 {
+            Promise::new(buyer_id).transfer(remaining);
+        }
+
+        purchased
+    }
+
+    //updates the prices of several of the caller's sales. If any of them fails, none of the prices are updated
+    #[payable]
+    pub fn update_prices(&mut self, updates: Vec<PriceUpdate>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        assert!(updates.len() <= MAX_BATCH_SIZE, "Cannot update more than {} sales at once", MAX_BATCH_SIZE);
+
+        let owner_id = env::predecessor_account_id();
+        for PriceUpdate { nft_contract_id, token_id, price } in updates {
+            self.internal_update_price(&owner_id, nft_contract_id, token_id, price);
+        }
+    }
+
+    //removes several of the caller's sales. If any of them fails, none of the sales are removed
+    #[payable]
+    pub fn remove_sales(&mut self, sales: Vec<SaleKey>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        assert!(sales.len() <= MAX_BATCH_SIZE, "Cannot remove more than {} sales at once", MAX_BATCH_SIZE);
+
+        let owner_id = env::predecessor_account_id();
+        for SaleKey { nft_contract_id, token_id } in sales {
+            self.internal_seller_remove_sale(&owner_id, nft_contract_id, token_id);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::*;
+
+//every bundle has a unique ID which is an incrementing number
+pub type BundleId = u64;
+
+//the GAS reserved in the bundle payouts callback for checking the payout of every token in the bundle
+const GAS_FOR_RESOLVE_BUNDLE_PAYOUTS_PER_ITEM: Gas = Gas(5_000_000_000_000);
+//the GAS reserved in the bundle purchase callback for paying out every token in the bundle
+const GAS_FOR_RESOLVE_BUNDLE_PURCHASE_PER_ITEM: Gas = Gas(35_000_000_000_000);
+
+//struct that holds one of the tokens in a bundle
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BundleItem {
+    pub nft_contract_id: AccountId,                 //nft contract where the token was minted
+    pub token_id: TokenId,                          //actual token ID in the bundle
+    pub approval_id: u64,                           //market contract's approval ID to transfer the token on behalf of the owner
+    pub weight: u32,                                //share of the bundle price paid out for this token, relative to the other tokens' weights
+}
+
+//struct that holds several tokens (possibly from several nft contracts) that are sold together for a single price
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bundle {
+    pub bundle_id: U64,                             //unique ID of the bundle
+    pub owner_id: AccountId,                        //owner of the bundle and all its tokens
+    pub price: U128,                                //price in yoctoNEAR for all the tokens together
+    pub items: Vec<BundleItem>,                     //tokens in the bundle
+}
+
+impl Bundle {
+    //splits an amount between the tokens in the bundle according to their weights. The last token gets whatever is left from rounding
+    pub(crate) fn split(&self, amount: Balance) -> Vec<Balance> {
+        let total_weight: u128 = self.items.iter().map(|item| u128::from(item.weight)).sum();
+        let mut remaining = amount;
+        let mut shares: Vec<Balance> = self.items
+            .iter()
+            .take(self.items.len().saturating_sub(1))
+            .map(|item| {
+                let share = amount * u128::from(item.weight) / total_weight;
+                remaining -= share;
+                share
+            })
+            .collect();
+        shares.push(remaining);
+        shares
+    }
+}
+
+// struct for the args passed in the `msg` of nft_approve when the owner adds a token to one of their bundles
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AddToBundleArgs {
+    pub bundle_id: U64,
+    pub weight: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        create an empty bundle sold for the given price. Tokens are added to the bundle by calling nft_approve on
+        their nft contract with the bundle ID and the token's weight as the msg. The bundle needs storage for itself
+        and for every token in it.
+    */
+    pub fn create_bundle(&mut self, price: U128) -> U64 {
+        self.assert_not_paused();
+        assert!(price.0 > 0, "Bundle price must be greater than 0");
+
+        let owner_id = env::predecessor_account_id();
+        //we need to enforce that the user has enough storage for 1 EXTRA entry
+        self.assert_storage_available(&owner_id);
+
+        let bundle_id = self.next_bundle_id;
+        self.next_bundle_id += 1;
+        self.bundles.insert(&bundle_id, &Bundle { bundle_id: U64(bundle_id), owner_id: owner_id.clone(), price, items: vec![] });
+
+        //get the bundle IDs for the owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.bundles_by_owner_id.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::BundlesByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_owner_id.insert(&bundle_id);
+        self.bundles_by_owner_id.insert(&owner_id, &by_owner_id);
+
+        U64(bundle_id)
+    }
+
+    //removes a bundle from the market. None of its tokens are transferred
+    #[payable]
+    pub fn remove_bundle(&mut self, bundle_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        let bundle = self.internal_remove_bundle(bundle_id.0);
+        //make sure the caller is the owner of the bundle. If this fails, the removal will revert
+        assert_eq!(env::predecessor_account_id(), bundle.owner_id, "Must be bundle owner");
+    }
+
+    /*
+        purchase every token in a bundle for the bundle price. The price is split between the tokens by their weights and the
+        payout of every token is queried with nft_payout and checked against the payout policy before any token is transferred.
+        Tokens with a valid payout are transferred through nft_transfer_payout. The buyer is refunded the share of the price of
+        every token whose payout was rejected or whose transfer failed and keeps the tokens that were transferred.
+    */
+    #[payable]
+    pub fn offer_bundle(&mut self, bundle_id: U64) -> Promise {
+        self.assert_not_paused();
+        let deposit = env::attached_deposit();
+        let buyer_id = env::predecessor_account_id();
+
+        let bundle = self.bundles.get(&bundle_id.0).expect("No bundle");
+        assert!(!bundle.items.is_empty(), "Bundle is empty");
+        assert_ne!(bundle.owner_id, buyer_id, "Cannot buy your own bundle");
+        assert!(deposit >= bundle.price.0, "Attached deposit must be greater than or equal to the bundle price: {:?}", bundle.price);
+
+        //take the bundle down and refund any overpayment
+        let bundle = self.internal_remove_bundle(bundle_id.0);
+        if deposit > bundle.price.0 {
+            Promise::new(buyer_id.clone()).transfer(deposit - bundle.price.0);
+        }
+
+        //get the payout of every token at once. Every token's share of the price minus its share of the fee is paid out through its royalties
+        let purchases = self.internal_bundle_purchases(&bundle);
+        let mut payouts: Option<Promise> = None;
+        for (price, context) in purchases.iter() {
+            let payout = self.internal_nft_payout(context, *price);
+            payouts = Some(match payouts {
+                Some(payouts) => payouts.and(payout),
+                None => payout,
+            });
+        }
+
+        //the purchases (and the fee taken from them) are passed along so the payouts are checked against the fee at the time of purchase
+        let items = purchases.len() as u64;
+        payouts.unwrap().then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(Gas((GAS_FOR_RESOLVE_BUNDLE_PAYOUTS_PER_ITEM.0 + GAS_FOR_NFT_TRANSFER.0 + GAS_FOR_RESOLVE_BUNDLE_PURCHASE_PER_ITEM.0) * items))
+            .resolve_bundle_payouts(buyer_id, purchases)
+        )
+    }
+
+    /*
+        private method used to resolve the promises when calling nft_payout for every token in a bundle. Every payout is checked
+        like the payout of a regular purchase: tokens with a valid payout are transferred and the buyer is refunded the share
+        of every token whose payout was rejected. The purchases hold every token's share of the price and the fee taken at the time of purchase.
+    */
+    #[private]
+    pub fn resolve_bundle_payouts(&mut self, buyer_id: AccountId, purchases: Vec<(U128, PurchaseContext)>) -> PromiseOrValue<U128> {
+        let mut transfers: Option<Promise> = None;
+        let mut checked = vec![];
+        let mut refunded = 0;
+        for (i, (price, context)) in purchases.into_iter().enumerate() {
+            //get the payout returned for the token. If the call failed, the buyer is refunded for the token's share
+            let result = match env::promise_result(i as u64) {
+                PromiseResult::Successful(value) => Some(value),
+                _ => None,
+            };
+            match self.internal_check_payout(result, price, &context) {
+                Ok(payout) => {
+                    let transfer = self.internal_nft_transfer_payout(&context, buyer_id.clone(), price);
+                    transfers = Some(match transfers {
+                        Some(transfers) => transfers.and(transfer),
+                        None => transfer,
+                    });
+                    checked.push(CheckedPurchase { price, payout, context });
+                }
+                Err(reason) => {
+                    self.internal_refund_purchase(buyer_id.clone(), price, &context, reason);
+                    refunded += price.0;
+                }
+            }
+        }
+
+        //if none of the payouts were valid, the whole bundle price has been refunded
+        let transfers = if let Some(transfers) = transfers {
+            transfers
+        } else {
+            return PromiseOrValue::Value(U128(refunded));
+        };
+
+        PromiseOrValue::Promise(transfers.then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(Gas(GAS_FOR_RESOLVE_BUNDLE_PURCHASE_PER_ITEM.0 * checked.len() as u64))
+            .resolve_bundle_purchase(buyer_id, checked)
+        ))
+    }
+
+    /*
+        private method used to resolve the promises when calling nft_transfer_payout for every token in a bundle whose payout was
+        checked. Every token is resolved like a regular purchase: the checked payouts are paid and the buyer is refunded the share
+        of any token that failed to transfer. Returns the total price of the tokens.
+    */
+    #[private]
+    pub fn resolve_bundle_purchase(&mut self, buyer_id: AccountId, purchases: Vec<CheckedPurchase>) -> U128 {
+        let mut total = 0;
+        for (i, purchase) in purchases.into_iter().enumerate() {
+            //get the payout returned for the token. If the transfer failed, the buyer is refunded for the token's share
+            let result = match env::promise_result(i as u64) {
+                PromiseResult::Successful(value) => Some(value),
+                _ => None,
+            };
+            total += self.internal_resolve_purchase(result, buyer_id.clone(), purchase).0;
+        }
+
+        U128(total)
+    }
+
+    // views
+    //returns the bundle for a given bundle ID
+    pub fn get_bundle(&self, bundle_id: U64) -> Option<Bundle> {
+        self.bundles.get(&bundle_id.0)
+    }
+
+    //returns the bundle a token is in (if any)
+    pub fn get_bundle_for_token(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Bundle> {
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        self.bundle_by_token.get(&contract_and_token_id).and_then(|bundle_id| self.bundles.get(&bundle_id))
+    }
+
+    //returns paginated bundles for a given account
+    pub fn get_bundles_by_owner_id(&self, account_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<Bundle> {
+        //get the set of bundle IDs for the given account. If there wasn't a set, return an empty vector
+        let by_owner_id = if let Some(by_owner_id) = self.bundles_by_owner_id.get(&account_id) {
+            by_owner_id
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_owner_id.iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 0
+            .take(limit.unwrap_or(0) as usize)
+            //we'll map the bundle IDs into Bundle objects
+            .map(|bundle_id| self.bundles.get(&bundle_id).unwrap())
+            .collect()
+    }
+}
+
+impl Contract {
+    //internal method called from nft_on_approve when the owner adds a token to one of their bundles (or updates its weight)
+    pub(crate) fn internal_add_to_bundle(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        args: AddToBundleArgs,
+    ) {
+        let mut bundle = self.bundles.get(&args.bundle_id.0).expect("No bundle");
+        assert_eq!(bundle.owner_id, owner_id, "Must be bundle owner");
+        assert!(args.weight > 0, "Weight must be greater than 0");
+
+        //make sure the token isn't in another bundle
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        if let Some(bundle_id) = self.bundle_by_token.get(&contract_and_token_id) {
+            assert_eq!(bundle_id, args.bundle_id.0, "Token is already in another bundle");
+        }
+
+        //the token can't be listed on its own and in a bundle at the same time
+        if self.sales.get(&contract_and_token_id).is_some() {
+            self.internal_cancel_sale(nft_contract_id.clone(), token_id.clone(), "bundled");
+        }
+
+        let item = BundleItem { nft_contract_id, token_id, approval_id, weight: args.weight };
+        match bundle.items.iter_mut().find(|i| i.nft_contract_id == item.nft_contract_id && i.token_id == item.token_id) {
+            //the token is already in the bundle so we update its approval ID and weight
+            Some(existing) => *existing = item,
+            None => {
+                assert!(bundle.items.len() < MAX_BUNDLE_ITEMS, "A bundle can't have more than {} tokens", MAX_BUNDLE_ITEMS);
+                //we need to enforce that the user has enough storage for 1 EXTRA entry
+                self.assert_storage_available(&owner_id);
+                self.bundle_by_token.insert(&contract_and_token_id, &args.bundle_id.0);
+                bundle.items.push(item);
+            }
+        }
+        self.bundles.insert(&args.bundle_id.0, &bundle);
+    }
+
+    //internal method for removing a bundle from the market. This returns the previously removed bundle object
+    pub(crate) fn internal_remove_bundle(&mut self, bundle_id: BundleId) -> Bundle {
+        let bundle = self.bundles.remove(&bundle_id).expect("No bundle");
+
+        //remove the bundle ID from the owner's set and remove the set if it's now empty
+        let mut by_owner_id = self.bundles_by_owner_id.get(&bundle.owner_id).expect("No bundle by owner_id");
+        by_owner_id.remove(&bundle_id);
+        if by_owner_id.is_empty() {
+            self.bundles_by_owner_id.remove(&bundle.owner_id);
+        } else {
+            self.bundles_by_owner_id.insert(&bundle.owner_id, &by_owner_id);
+        }
+
+        //the tokens are no longer in a bundle
+        for item in bundle.items.iter() {
+            self.bundle_by_token.remove(&format!("{}{}{}", item.nft_contract_id, DELIMITER, item.token_id));
+        }
+
+        bundle
+    }
+
+    //returns the share of the bundle price and the purchase details for every token in the bundle
+    pub(crate) fn internal_bundle_purchases(&self, bundle: &Bundle) -> Vec<(U128, PurchaseContext)> {
+        bundle.items
+            .iter()
+            .zip(bundle.split(bundle.price.0))
+            .map(|(item, price)| (U128(price), PurchaseContext {
+                nft_contract_id: item.nft_contract_id.clone(),
+                token_id: item.token_id.clone(),
+                approval_id: item.approval_id,
+                seller_id: bundle.owner_id.clone(),
+                fee: U128(self.internal_fee_for(price)),
+                ft_token_id: None,
+            }))
+            .collect()
+    }
+
+    //internal method for taking down the bundle a token is in, if any. This is used when the token's approval changes
+    pub(crate) fn internal_remove_bundle_for_token(&mut self, nft_contract_id: &AccountId, token_id: &TokenId) {
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        if let Some(bundle_id) = self.bundle_by_token.get(&contract_and_token_id) {
+            self.internal_remove_bundle(bundle_id);
+        }
+    }
+
+    //returns how much storage entries an account is using for its bundles. Every bundle and every token in it is one entry
+    pub(crate) fn internal_bundle_entries(&self, account_id: &AccountId) -> u64 {
+        self.bundles_by_owner_id
+            .get(account_id)
+            .map(|by_owner_id| by_owner_id.iter().map(|bundle_id| 1 + self.bundles.get(&bundle_id).unwrap().items.len() as u64).sum())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,278 @@
+use crate::*;
+
+//every buy offer has a unique ID which is an incrementing number
+pub type BuyOfferId = u64;
+
+//struct that holds a standing offer to buy a token (or any token from a collection) that may not be listed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuyOffer {
+    pub offer_id: U64,                              //unique ID of the offer
+    pub buyer_id: AccountId,                        //account that placed the offer and will receive the token
+    pub nft_contract_id: AccountId,                 //nft contract the offer is for
+    pub token_id: Option<TokenId>,                  //token the offer is for. If None, the offer is for any token in the collection
+    pub amount: U128,                               //amount in yoctoNEAR escrowed by the marketplace
+    pub expires_at: Option<U64>,                    //block timestamp (in nanoseconds) after which the offer can no longer be accepted
+}
+
+impl BuyOffer {
+    //returns true if the offer has passed its expiry
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0)
+    }
+}
+
+// struct for the args passed in the `msg` of nft_approve when the owner accepts a buy offer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptOfferArgs {
+    pub accept_offer_id: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        place a standing offer to buy a token. If no token ID is passed in, the offer is for any token from the
+        nft contract. The attached deposit is escrowed until the offer is accepted, cancelled or expires.
+        The buyer needs to have paid storage for the offer just like a seller does for a sale.
+    */
+    #[payable]
+    pub fn place_buy_offer(&mut self, nft_contract_id: AccountId, token_id: Option<TokenId>, expires_at: Option<U64>) -> U64 {
+        //get the attached deposit and make sure it's greater than 0
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Attached deposit must be greater than 0");
+        //make sure the marketplace isn't paused and the nft contract can be traded here
+        self.assert_not_paused();
+        self.assert_nft_contract_allowed(&nft_contract_id);
+
+        //make sure the offer isn't already expired
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Offer must expire in the future");
+        }
+
+        //make sure the buyer has paid enough storage for 1 EXTRA entry on the market
+        let buyer_id = env::predecessor_account_id();
+        self.assert_storage_available(&buyer_id);
+
+        //create the offer with the next unique ID
+        let offer_id = self.next_buy_offer_id;
+        self.next_buy_offer_id += 1;
+        let offer = BuyOffer {
+            offer_id: U64(offer_id),
+            buyer_id: buyer_id.clone(),
+            nft_contract_id: nft_contract_id.clone(),
+            token_id,
+            amount: U128(amount),
+            expires_at,
+        };
+        self.buy_offers.insert(&offer_id, &offer);
+
+        //Extra functionality that populates collections necessary for the view calls
+        //get the offers for the given buyer. If there are none, we create a new empty set
+        let mut by_buyer_id = self.buy_offers_by_buyer_id.get(&buyer_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::BuyOffersByBuyerIdInner {
+                    //we get a new unique prefix for the collection by hashing the buyer
+                    account_id_hash: hash_account_id(&buyer_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_buyer_id.insert(&offer_id);
+        self.buy_offers_by_buyer_id.insert(&buyer_id, &by_buyer_id);
+
+        //get the offers for the given nft contract. If there are none, we create a new empty set
+        let mut by_nft_contract_id = self.buy_offers_by_nft_contract_id.get(&nft_contract_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::BuyOffersByNFTContractIdInner {
+                    //we get a new unique prefix for the collection by hashing the nft contract
+                    account_id_hash: hash_account_id(&nft_contract_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_nft_contract_id.insert(&offer_id);
+        self.buy_offers_by_nft_contract_id.insert(&nft_contract_id, &by_nft_contract_id);
+
+        U64(offer_id)
+    }
+
+    //cancels a buy offer and refunds the escrowed amount. The buyer can cancel at any time, anyone can clean up an expired offer
+    #[payable]
+    pub fn cancel_buy_offer(&mut self, offer_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //get the offer object as the return value from removing the offer internally
+        let offer = self.internal_remove_buy_offer(offer_id.0);
+        //if this fails, the removal will revert
+        assert!(
+            env::predecessor_account_id() == offer.buyer_id || offer.is_expired(),
+            "Must be offer owner or offer must be expired"
+        );
+        //refund the escrowed amount to the buyer
+        Promise::new(offer.buyer_id).transfer(offer.amount.0);
+    }
+
+    // views
+
+    //get the buy offer for a given offer ID
+    pub fn get_buy_offer(&self, offer_id: U64) -> Option<BuyOffer> {
+        self.buy_offers.get(&offer_id.0)
+    }
+
+    //returns paginated buy offers placed by a given account
+    pub fn get_buy_offers_by_buyer_id(&self, account_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<BuyOffer> {
+        //get the set of offer IDs for the given account. If there isn't one, return an empty vector
+        let by_buyer_id = if let Some(by_buyer_id) = self.buy_offers_by_buyer_id.get(&account_id) {
+            by_buyer_id
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_buyer_id.as_vector().iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(0) as usize)
+            .map(|offer_id| self.buy_offers.get(&offer_id).unwrap())
+            .collect()
+    }
+
+    //returns paginated buy offers (token and collection offers) for a given nft contract
+    pub fn get_buy_offers_by_nft_contract_id(&self, nft_contract_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<BuyOffer> {
+        //get the set of offer IDs for the given nft contract. If there isn't one, return an empty vector
+        let by_nft_contract_id = if let Some(by_nft_contract_id) = self.buy_offers_by_nft_contract_id.get(&nft_contract_id) {
+            by_nft_contract_id
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_nft_contract_id.as_vector().iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(0) as usize)
+            .map(|offer_id| self.buy_offers.get(&offer_id).unwrap())
+            .collect()
+    }
+
+    /*
+        returns the buy offers that can currently be accepted for a specific token (including collection offers).
+        from_index and limit page through all of the nft contract's offers, so a page only holds the offers
+        in that range which match the token and may be shorter than limit. Keep paging until from_index
+        reaches get_supply_buy_offers_by_nft_contract_id.
+    */
+    pub fn get_buy_offers_for_token(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<BuyOffer> {
+        //get the set of offer IDs for the given nft contract. If there isn't one, return an empty vector
+        let by_nft_contract_id = if let Some(by_nft_contract_id) = self.buy_offers_by_nft_contract_id.get(&nft_contract_id) {
+            by_nft_contract_id
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_nft_contract_id.as_vector().iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(0) as usize)
+            .map(|offer_id| self.buy_offers.get(&offer_id).unwrap())
+            .filter(|offer| !offer.is_expired() && offer.token_id.as_ref().is_none_or(|id| id == &token_id))
+            .collect()
+    }
+
+    //returns the number of buy offers placed by a given account
+    pub fn get_supply_buy_offers_by_buyer_id(&self, account_id: AccountId) -> U64 {
+        U64(self.buy_offers_by_buyer_id.get(&account_id).map_or(0, |by_buyer_id| by_buyer_id.len()))
+    }
+
+    //returns the number of buy offers (token and collection offers) for a given nft contract
+    pub fn get_supply_buy_offers_by_nft_contract_id(&self, nft_contract_id: AccountId) -> U64 {
+        U64(self.buy_offers_by_nft_contract_id.get(&nft_contract_id).map_or(0, |by_nft_contract_id| by_nft_contract_id.len()))
+    }
+}
+
+impl Contract {
+    //internal method for removing a buy offer from the market. This returns the previously removed offer object
+    pub(crate) fn internal_remove_buy_offer(&mut self, offer_id: BuyOfferId) -> BuyOffer {
+        //get the offer object by removing the offer ID. If there was no offer, panic
+        let offer = self.buy_offers.remove(&offer_id).expect("No buy offer");
+
+        //remove the offer ID from the buyer's set and remove the set if it's now empty
+        let mut by_buyer_id = self.buy_offers_by_buyer_id.get(&offer.buyer_id).expect("No buy offer by buyer_id");
+        by_buyer_id.remove(&offer_id);
+        if by_buyer_id.is_empty() {
+            self.buy_offers_by_buyer_id.remove(&offer.buyer_id);
+        } else {
+            self.buy_offers_by_buyer_id.insert(&offer.buyer_id, &by_buyer_id);
+        }
+
+        //remove the offer ID from the nft contract's set and remove the set if it's now empty
+        let mut by_nft_contract_id = self
+            .buy_offers_by_nft_contract_id
+            .get(&offer.nft_contract_id)
+            .expect("No buy offer by nft_contract_id");
+        by_nft_contract_id.remove(&offer_id);
+        if by_nft_contract_id.is_empty() {
+            self.buy_offers_by_nft_contract_id.remove(&offer.nft_contract_id);
+        } else {
+            self.buy_offers_by_nft_contract_id.insert(&offer.nft_contract_id, &by_nft_contract_id);
+        }
+
+        //return the offer object
+        offer
+    }
+
+    /*
+        internal method called from nft_on_approve when the token owner accepts a buy offer. The offer is removed
+        and the token is transferred to the buyer through the regular payout flow using the approval the owner just gave.
+    */
+    pub(crate) fn internal_accept_buy_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        offer_id: BuyOfferId,
+    ) -> Promise {
+        //get the offer object by removing the offer
+        let offer = self.internal_remove_buy_offer(offer_id);
+
+        //make sure the offer is for this token (or for any token in this collection) and can still be accepted
+        assert_eq!(offer.nft_contract_id, nft_contract_id, "Offer is for a different nft contract");
+        if let Some(offer_token_id) = offer.token_id.as_ref() {
+            assert_eq!(offer_token_id, &token_id, "Offer is for a different token");
+        }
+        assert!(!offer.is_expired(), "Offer has expired");
+        assert_ne!(offer.buyer_id, owner_id, "Cannot accept your own offer");
+
+        //if the token was also listed, the listing is now stale so we take it down
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        if let Some(sale) = self.sales.get(&contract_and_token_id) {
+            //bidders' funds are held by the market so an auction can't be taken down once someone has bid
+            assert!(!sale.auction.is_some_and(|a| a.has_bids()), "Token is in an auction with active bids");
+            self.internal_cancel_sale(nft_contract_id.clone(), token_id.clone(), "offer_accepted");
+        }
+
+        //transfer the token to the buyer and pay the owner (and royalties) out of the escrowed amount
+        let context = PurchaseContext {
+            nft_contract_id,
+            token_id,
+            approval_id,
+            seller_id: owner_id,
+            fee: U128(self.internal_fee_for(offer.amount.0)),
+            ft_token_id: None,
+        };
+        self.internal_transfer_payout(context, offer.amount, offer.buyer_id)
+    }
+}
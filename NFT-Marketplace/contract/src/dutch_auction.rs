@@ -0,0 +1,85 @@
+use crate::*;
+
+//256 bit unsigned integer used to multiply a yoctoNEAR price by a time in nanoseconds without overflowing
+#[allow(clippy::manual_div_ceil, clippy::assign_op_pattern)]
+mod u256 {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+use u256::U256;
+
+//struct for the dutch auction parameters passed in the `msg` of nft_approve. The sale conditions are the starting price
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuctionArgs {
+    pub starts_at: U64,                             //block timestamp (in nanoseconds) when the price starts dropping and the sale opens
+    pub ends_at: U64,                               //block timestamp (in nanoseconds) when the price reaches the floor
+    pub floor_price: U128,                          //the lowest price the sale drops to. The sale stays at this price after it ends
+    pub step_interval: Option<U64>,                 //if present, the price drops in steps of this length (in nanoseconds) instead of linearly
+}
+
+//struct that holds the price schedule of a dutch auction attached to a sale
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuction {
+    pub starts_at: U64,
+    pub ends_at: U64,
+    pub floor_price: U128,
+    pub step_interval: Option<U64>,
+}
+
+impl DutchAuction {
+    //create a new dutch auction from the listing args. Panics if the time window or prices are invalid
+    pub(crate) fn new(args: DutchAuctionArgs, start_price: Balance) -> Self {
+        assert!(args.ends_at.0 > args.starts_at.0, "Dutch auction must end after it starts");
+        assert!(args.ends_at.0 > env::block_timestamp(), "Dutch auction must end in the future");
+        assert!(args.floor_price.0 < start_price, "Floor price must be lower than the starting price");
+        if let Some(step_interval) = args.step_interval {
+            assert!(
+                step_interval.0 > 0 && step_interval.0 <= args.ends_at.0 - args.starts_at.0,
+                "Step interval must be greater than 0 and no longer than the auction"
+            );
+        }
+
+        Self {
+            starts_at: args.starts_at,
+            ends_at: args.ends_at,
+            floor_price: args.floor_price,
+            step_interval: args.step_interval,
+        }
+    }
+
+    //the price at the current block timestamp. It drops from the starting price to the floor price between the start and end times
+    pub(crate) fn current_price(&self, start_price: Balance) -> Balance {
+        let now = env::block_timestamp();
+        if now <= self.starts_at.0 {
+            return start_price;
+        }
+        if now >= self.ends_at.0 {
+            return self.floor_price.0;
+        }
+
+        //the time that has passed since the start. With steps, the price only drops at the end of every full step
+        let duration = self.ends_at.0 - self.starts_at.0;
+        let mut elapsed = now - self.starts_at.0;
+        if let Some(step_interval) = self.step_interval {
+            elapsed -= elapsed % step_interval.0;
+        }
+
+        //the product of the price range and the elapsed nanoseconds doesn't fit in a u128 for NEAR scale prices
+        let drop = U256::from(start_price - self.floor_price.0) * U256::from(elapsed) / U256::from(duration);
+        start_price - drop.as_u128()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // views
+    //returns the price a sale can currently be purchased for. For dutch auctions this is the decayed price, for auctions the starting price
+    pub fn get_current_price(&self, nft_contract_id: AccountId, token_id: TokenId) -> U128 {
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        U128(sale.current_price())
+    }
+}
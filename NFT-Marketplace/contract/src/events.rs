@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fmt;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+
+// Enum that represents the data type of the EventLog
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[serde(crate = "near_sdk::serde")]
+#[non_exhaustive]
+#[allow(clippy::enum_variant_names)]
+pub enum EventLogVariant {
+    SaleList(Vec<SaleListLog>),
+    SaleUpdatePrice(Vec<SaleUpdatePriceLog>),
+    SaleDelist(Vec<SaleDelistLog>),
+    SalePurchase(Vec<SalePurchaseLog>),
+    SalePurchaseFailed(Vec<SalePurchaseFailedLog>),
+    SaleSwap(Vec<SaleSwapLog>),
+    SaleSwapFailed(Vec<SaleSwapLog>),
+    SaleLazyMint(Vec<SaleLazyMintLog>),
+    EscrowClaimable(Vec<EscrowClaimableLog>),
+}
+
+/// Interface to capture data about an event
+///
+/// Arguments:
+/// * `standard`: name of standard e.g. nft_market
+/// * `version`: e.g. 1.0.0
+/// * `event`: associate event data
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+    // `flatten` to not have "event": {<EventLogVariant>} in the JSON, just have the contents of {<EventLogVariant>}.
+    #[serde(flatten)]
+    pub event: EventLogVariant,
+}
+
+impl fmt::Display for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "EVENT_JSON:{}",
+            &serde_json::to_string(self).map_err(|_| fmt::Error)?
+        ))
+    }
+}
+
+/// An event log to capture a token being listed
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+/// * `price`: "1000000000000000000000000"
+/// * `ft_token_id`: optional fungible token the sale is priced in
+/// * `is_auction`: whether the token is listed as an auction
+/// * `reserved_for`: optional accounts that can purchase a private sale
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleListLog {
+    pub owner_id: String,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub price: U128,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<String>,
+
+    pub is_auction: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved_for: Option<Vec<String>>,
+}
+
+/// An event log to capture the price of a sale changing
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+/// * `old_price`: "1000000000000000000000000"
+/// * `new_price`: "2000000000000000000000000"
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleUpdatePriceLog {
+    pub owner_id: String,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub old_price: U128,
+    pub new_price: U128,
+}
+
+/// An event log to capture a sale being taken down without being purchased
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+/// * `reason`: why the sale was taken down e.g. "removed_by_owner"
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleDelistLog {
+    pub owner_id: String,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub reason: String,
+}
+
+/// An event log to capture a completed purchase
+///
+/// Arguments
+/// * `buyer_id`: "buyer.near"
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+/// * `price`: total paid by the buyer
+/// * `ft_token_id`: optional fungible token the sale was paid in
+/// * `fee`: amount kept by the marketplace
+/// * `payout`: amount paid to every account (seller and royalties)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SalePurchaseLog {
+    pub buyer_id: String,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub price: U128,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<String>,
+
+    pub fee: U128,
+    pub payout: HashMap<String, U128>,
+}
+
+/// An event log to capture a purchase that failed and was refunded to the buyer
+///
+/// Arguments
+/// * `buyer_id`: "buyer.near"
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+/// * `price`: amount refunded to the buyer
+/// * `ft_token_id`: optional fungible token the refund is sent in
+/// * `reason`: why the purchase was rejected e.g. "invalid_payout"
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SalePurchaseFailedLog {
+    pub buyer_id: String,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub price: U128,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<String>,
+
+    pub reason: String,
+}
+
+/// An event log to capture a swap listing being exchanged for another token (or the exchange failing)
+///
+/// Arguments
+/// * `owner_id`: "owner.near", the seller of the listed token if it's known
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+/// * `counterparty_id`: "counterparty.near"
+/// * `swap_nft_contract_id`: "other-nft.near"
+/// * `swap_token_id`: "2"
+/// * `reason`: optional reason the swap failed e.g. "transfer_failed"
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleSwapLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
+
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub counterparty_id: String,
+    pub swap_nft_contract_id: String,
+    pub swap_token_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// An event log to capture a new edition of a series being minted to a buyer
+///
+/// Arguments
+/// * `owner_id`: "owner.near", the series owner
+/// * `buyer_id`: "buyer.near"
+/// * `nft_contract_id`: "nft.near"
+/// * `series_id`: "1"
+/// * `price`: total paid by the buyer
+/// * `fee`: amount kept by the marketplace
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleLazyMintLog {
+    pub owner_id: String,
+    pub buyer_id: String,
+    pub nft_contract_id: String,
+    pub series_id: U64,
+    pub price: U128,
+    pub fee: U128,
+}
+
+/// An event log to capture a token the market holds in escrow after sending it out failed. It can be claimed with claim_escrowed_token
+///
+/// Arguments
+/// * `owner_id`: "owner.near", the account that can claim the token
+/// * `nft_contract_id`: "nft.near"
+/// * `token_id`: "1"
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EscrowClaimableLog {
+    pub owner_id: String,
+    pub nft_contract_id: String,
+    pub token_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nep_format_purchase() {
+        let expected = r#"EVENT_JSON:{"standard":"nft_market","version":"1.0.0","event":"sale_purchase","data":[{"buyer_id":"buyer.near","nft_contract_id":"nft.near","token_id":"1","price":"1000","fee":"25","payout":{"seller.near":"975"}}]}"#;
+        let log = EventLog {
+            standard: "nft_market".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::SalePurchase(vec![SalePurchaseLog {
+                buyer_id: "buyer.near".to_string(),
+                nft_contract_id: "nft.near".to_string(),
+                token_id: "1".to_string(),
+                price: U128(1000),
+                ft_token_id: None,
+                fee: U128(25),
+                payout: HashMap::from([("seller.near".to_string(), U128(975))]),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_delist() {
+        let expected = r#"EVENT_JSON:{"standard":"nft_market","version":"1.0.0","event":"sale_delist","data":[{"owner_id":"owner.near","nft_contract_id":"nft.near","token_id":"1","reason":"expired"}]}"#;
+        let log = EventLog {
+            standard: "nft_market".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::SaleDelist(vec![SaleDelistLog {
+                owner_id: "owner.near".to_string(),
+                nft_contract_id: "nft.near".to_string(),
+                token_id: "1".to_string(),
+                reason: "expired".to_string(),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+}
@@ -2,6 +2,14 @@ use crate::*;
 
 /// external contract calls
 
+//the parts of the token returned by nft_token that the market needs to check whether a sale is still valid
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonToken {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+}
+
 //initiate a cross contract call to the nft contract. This will transfer the token to the buyer and return
 //a payout object used for the market to distribute funds to the appropriate accounts.
 #[ext_contract(ext_contract)]
@@ -20,4 +28,29 @@ trait ExtContract {
         //the maximum amount of accounts the market can payout at once (this is limited by GAS)
 		max_len_payout: u32,
     );
-}
\ No newline at end of file
+
+    //get the payout for a token without transferring it. This is used to check the payout of a purchase before the token is
+    //transferred and to cache the royalty schedule of verified nft contracts
+    fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32);
+
+    //mint a new edition of a series to the receiver. This is used to sell lazy minted editions
+    fn nft_mint(&mut self, id: U64, receiver_id: AccountId);
+
+    //get information about a series. This is used to check who owns a series before it's listed for lazy minting
+    fn get_series_details(&self, id: u64);
+
+    //get information about the token. This is used to check whether a sale has gone stale
+    fn nft_token(&self, token_id: TokenId);
+}
+
+//cross contract call to a fungible token contract. This is used to pay out sales that were priced in fungible tokens
+//and to refund buyers if a purchase fails.
+#[ext_contract(ext_ft_contract)]
+trait ExtFtContract {
+    fn ft_transfer(
+        &mut self,
+        receiver_id: AccountId, //account to send the tokens to
+        amount: U128, //amount of tokens to send
+        memo: Option<String>, //memo (to include some context)
+    );
+}
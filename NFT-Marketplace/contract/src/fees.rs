@@ -0,0 +1,149 @@
+use crate::*;
+use near_sdk::is_promise_success;
+
+//the fee is expressed in basis points. 10 000 basis points is 100%
+const MAX_FEE_BASIS_POINTS: u32 = 10_000;
+
+//struct that keeps track of the lifetime volume and fees of all the sales for a given nft contract
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionVolume {
+    pub sales_count: u64,                           //number of completed sales
+    pub near_volume: U128,                          //total yoctoNEAR paid by buyers
+    pub near_fees: U128,                            //total yoctoNEAR taken by the marketplace as fees
+    pub ft_volume: HashMap<FungibleTokenId, U128>,  //total paid by buyers for each fungible token
+    pub ft_fees: HashMap<FungibleTokenId, U128>,    //total taken by the marketplace as fees for each fungible token
+}
+
+impl Default for CollectionVolume {
+    fn default() -> Self {
+        Self {
+            sales_count: 0,
+            near_volume: U128(0),
+            near_fees: U128(0),
+            ft_volume: HashMap::new(),
+            ft_fees: HashMap::new(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Allows the owner to set the fee (in basis points) that is taken from every sale before royalties are paid out
+    pub fn set_fee_basis_points(&mut self, fee_basis_points: u32) {
+        //make sure only the owner can change the fee
+        self.assert_owner();
+        assert!(fee_basis_points < MAX_FEE_BASIS_POINTS, "Fee must be less than {} basis points", MAX_FEE_BASIS_POINTS);
+        self.fee_basis_points = fee_basis_points;
+    }
+
+    /*
+        Allows the owner to withdraw the accumulated fees to the owner account. If no amount is passed in, the whole balance is withdrawn.
+        Fungible token fees are put back in the treasury if the transfer fails (e.g. the owner isn't registered on the token contract)
+    */
+    #[payable]
+    pub fn withdraw_fees(&mut self, ft_token_id: Option<FungibleTokenId>, amount: Option<U128>) -> PromiseOrValue<U128> {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //make sure only the owner can withdraw fees
+        self.assert_owner();
+
+        //get the fee balance for the currency being withdrawn
+        let balance = self.get_fee_balance(ft_token_id.clone()).0;
+        let amount = amount.map(|a| a.0).unwrap_or(balance);
+        assert!(amount > 0, "Nothing to withdraw");
+        assert!(amount <= balance, "Cannot withdraw more than the fee balance: {}", balance);
+
+        //subtract the amount from the treasury before sending it
+        match ft_token_id {
+            Some(ft_token_id) => {
+                self.ft_fee_balances.insert(&ft_token_id, &(balance - amount));
+                transfer_funds(Some(&ft_token_id), self.owner_id.clone(), amount)
+                .then(
+                    Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_WITHDRAW_FEES)
+                    .resolve_withdraw_fees(ft_token_id, U128(amount))
+                )
+                .into()
+            }
+            None => {
+                self.near_fee_balance = balance - amount;
+                transfer_funds(None, self.owner_id.clone(), amount);
+                PromiseOrValue::Value(U128(amount))
+            }
+        }
+    }
+
+    //private method used to resolve the promise when fungible token fees are withdrawn. If the transfer failed, the amount is put back in the treasury
+    #[private]
+    pub fn resolve_withdraw_fees(&mut self, ft_token_id: FungibleTokenId, amount: U128) -> U128 {
+        if is_promise_success() {
+            return amount;
+        }
+
+        let balance = self.ft_fee_balances.get(&ft_token_id).unwrap_or(0);
+        self.ft_fee_balances.insert(&ft_token_id, &(balance + amount.0));
+        U128(0)
+    }
+
+    // views
+    //return the fee (in basis points) taken from every sale
+    pub fn get_fee_basis_points(&self) -> u32 {
+        self.fee_basis_points
+    }
+
+    //return the accumulated fees that haven't been withdrawn yet. If no FT token ID is passed in, the NEAR balance is returned
+    pub fn get_fee_balance(&self, ft_token_id: Option<FungibleTokenId>) -> U128 {
+        U128(match ft_token_id {
+            Some(ft_token_id) => self.ft_fee_balances.get(&ft_token_id).unwrap_or(0),
+            None => self.near_fee_balance,
+        })
+    }
+
+    //return the lifetime volume and fees of all the sales for a given nft contract
+    pub fn get_volume_by_nft_contract_id(&self, nft_contract_id: AccountId) -> CollectionVolume {
+        self.volume_by_nft_contract_id.get(&nft_contract_id).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //calculate the marketplace fee for a given price
+    pub(crate) fn internal_fee_for(&self, price: Balance) -> Balance {
+        price * self.fee_basis_points as u128 / MAX_FEE_BASIS_POINTS as u128
+    }
+
+    //internal method called once a sale has been paid out. This adds the fee to the treasury and updates the volume for the nft contract
+    pub(crate) fn internal_record_sale(
+        &mut self,
+        nft_contract_id: &AccountId,
+        ft_token_id: Option<&FungibleTokenId>,
+        price: Balance,
+        fee: Balance,
+    ) {
+        //get the volume for the nft contract. If there isn't one, we start from 0
+        let mut volume = self.get_volume_by_nft_contract_id(nft_contract_id.clone());
+        volume.sales_count += 1;
+
+        match ft_token_id {
+            Some(ft_token_id) => {
+                //add the fee to the treasury for that token
+                let balance = self.ft_fee_balances.get(ft_token_id).unwrap_or(0);
+                self.ft_fee_balances.insert(ft_token_id, &(balance + fee));
+
+                //add the price and fee to the volume for that token
+                let ft_volume = volume.ft_volume.entry(ft_token_id.clone()).or_insert(U128(0));
+                ft_volume.0 += price;
+                let ft_fees = volume.ft_fees.entry(ft_token_id.clone()).or_insert(U128(0));
+                ft_fees.0 += fee;
+            }
+            None => {
+                self.near_fee_balance += fee;
+                volume.near_volume.0 += price;
+                volume.near_fees.0 += fee;
+            }
+        }
+
+        //insert the volume back into the map for the nft contract
+        self.volume_by_nft_contract_id.insert(nft_contract_id, &volume);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::*;
+
+// transfer callbacks from FT Contracts
+
+// struct for the purchase args passed in the `msg` of ft_transfer_call
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseArgs {
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+}
+
+/*
+    trait that will be used as the callback from the FT contract. When ft_transfer_call is
+    called, the tokens are transferred to the marketplace and this is the function
+    that is invoked.
+*/
+pub trait FungibleTokenReceiver {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
+}
+
+//implementation of the trait
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /*
+        where we purchase a sale that is priced in fungible tokens. The FT contract is the predecessor,
+        the sender is the buyer and the amount is how much they sent. Whatever we return is the amount
+        that wasn't used and the FT contract will refund it to the sender. If we panic, the sender is refunded in full.
+    */
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        // get the FT contract ID which is the predecessor
+        let ft_token_id = env::predecessor_account_id();
+        //make sure the marketplace isn't paused
+        self.assert_not_paused();
+
+        //make sure the token being paid with is on the marketplace whitelist
+        assert!(self.approved_ft_token_ids.contains(&ft_token_id), "FT token is not approved: {}", ft_token_id);
+
+        //the sale to purchase comes from the msg field. If it isn't valid, we panic and the buyer is refunded.
+        let PurchaseArgs { nft_contract_id, token_id } = near_sdk::serde_json::from_str(&msg).expect("Not valid PurchaseArgs");
+
+        //get the unique sale ID (contract + DELIMITER + token ID)
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //make sure the sale is priced in the token that was sent
+        assert_eq!(sale.ft_token_id.as_ref(), Some(&ft_token_id), "Sale is not priced in {}", ft_token_id);
+
+        //make sure the buyer isn't the owner of the sale
+        assert_ne!(sale.owner_id, sender_id, "Cannot bid on your own sale");
+        //private sales can only be purchased by the accounts they're reserved for
+        assert!(sale.is_reserved_for(&sender_id), "Sale is reserved for other accounts");
+        //swap listings can only be accepted with a token through nft_approve
+        assert!(sale.swap_for.is_none(), "Sale is a swap listing, accept it by approving a requested token");
+        //make sure the sale has started and hasn't expired
+        sale.assert_purchase_window();
+
+        //get the u128 price of the token. Dutch auction prices are computed at the current block timestamp
+        let price = sale.current_price();
+
+        //make sure the amount sent is greater than the price
+        assert!(amount.0 >= price, "Transferred amount must be greater than or equal to the current price: {:?}", price);
+
+        //process the purchase for exactly the sale price. The payout will be sent in the sale's FT token
+        self.process_purchase(
+            nft_contract_id,
+            token_id,
+            U128(price),
+            sender_id,
+        );
+
+        //return the excess so the FT contract refunds it to the buyer
+        PromiseOrValue::Value(U128(amount.0 - price))
+    }
+}
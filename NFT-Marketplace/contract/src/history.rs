@@ -0,0 +1,35 @@
+use crate::*;
+
+//struct that holds a completed sale in the recent sales of an nft contract
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleRecord {
+    pub buyer_id: AccountId,                        //account that bought the token
+    pub token_id: TokenId,                          //token that was sold
+    pub price: U128,                                //total paid by the buyer
+    pub ft_token_id: Option<FungibleTokenId>,       //fungible token the sale was paid in. If None, it was paid in NEAR
+    pub sold_at: U64,                               //block timestamp (in nanoseconds) when the sale was paid out
+}
+
+#[near_bindgen]
+impl Contract {
+    //returns the most recent completed sales for a given nft contract (newest first). At most SALE_HISTORY_LENGTH sales are kept
+    pub fn get_sale_history(&self, nft_contract_id: AccountId) -> Vec<SaleRecord> {
+        self.sale_history
+            .get(&nft_contract_id)
+            .map(|history| history.into_iter().rev().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for adding a completed sale to the history of an nft contract. Once the history is full, the oldest sale is dropped
+    pub(crate) fn internal_add_sale_history(&mut self, nft_contract_id: &AccountId, record: SaleRecord) {
+        let mut history = self.sale_history.get(nft_contract_id).unwrap_or_default();
+        if history.len() >= SALE_HISTORY_LENGTH {
+            history.pop_front();
+        }
+        history.push_back(record);
+        self.sale_history.insert(nft_contract_id, &history);
+    }
+}
@@ -9,7 +9,50 @@ pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     hash
 }
 
+//used to send funds to an account in whichever currency the sale was priced in. If there's no FT token ID, we send NEAR
+pub(crate) fn transfer_funds(ft_token_id: Option<&FungibleTokenId>, receiver_id: AccountId, amount: Balance) -> Promise {
+    if let Some(ft_token_id) = ft_token_id {
+        //ft_transfer requires exactly 1 yoctoNEAR to be attached
+        ext_ft_contract::ext(ft_token_id.clone())
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(receiver_id, U128(amount), Some("payout from market".to_string()))
+    } else {
+        Promise::new(receiver_id).transfer(amount)
+    }
+}
+
 impl Contract {
+    //used to make sure only the owner of the marketplace can call a method
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can call this method");
+    }
+
+    //returns how much storage an account is using for its sales, buy offers, lazy mint listings and bundles on the market
+    pub(crate) fn internal_storage_used(&self, account_id: &AccountId) -> Balance {
+        //get the number of sales, buy offers, lazy mint listings and bundle entries that the account has up
+        let len = self.by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default()
+            + self.buy_offers_by_buyer_id.get(account_id).map(|s| s.len()).unwrap_or_default()
+            + self.lazy_mints_by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default()
+            + self.internal_bundle_entries(account_id);
+        u128::from(len) * STORAGE_PER_SALE
+    }
+
+    //used to make sure an account has paid enough storage for 1 EXTRA sale or buy offer
+    pub(crate) fn assert_storage_available(&self, account_id: &AccountId) {
+        //get the total storage paid by the account
+        let paid_storage = self.storage_deposits.get(account_id).unwrap_or(0);
+        //get the storage required which is simply the storage currently used + 1 more entry
+        let storage_required = self.internal_storage_used(account_id) + STORAGE_PER_SALE;
+
+        //make sure that the total paid is >= the required storage
+        assert!(
+            paid_storage >= storage_required,
+            "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
+            paid_storage, storage_required / STORAGE_PER_SALE, STORAGE_PER_SALE
+        );
+    }
+
     //internal method for removing a sale from the market. This returns the previously removed sale object
     pub(crate) fn internal_remove_sale(
         &mut self,
@@ -52,7 +95,188 @@ impl Contract {
                 .insert(&nft_contract_id, &by_nft_contract_id);
         }
 
+        //remove the unique sale ID from the private sales of every account it was reserved for
+        for account_id in sale.reserved_for.iter().flatten() {
+            if let Some(mut by_reserved_for) = self.by_reserved_for.get(account_id) {
+                by_reserved_for.remove(&contract_and_token_id);
+                if by_reserved_for.is_empty() {
+                    self.by_reserved_for.remove(account_id);
+                } else {
+                    self.by_reserved_for.insert(account_id, &by_reserved_for);
+                }
+            }
+        }
+
+        //remove the sale from the price index
+        self.internal_unindex_price(&sale);
+
         //return the sale object
         sale
     }
-}
\ No newline at end of file
+
+    //returns the price a sale is ordered by in the price index. Only public fixed price NEAR sales are indexed since auction
+    //prices change with bids or over time, fungible token prices can't be compared to NEAR, swap listings don't have a price
+    //and private sales can't be bought by everyone
+    pub(crate) fn indexed_price(sale: &Sale) -> Option<Balance> {
+        if sale.auction.is_none() && sale.dutch_auction.is_none() && sale.swap_for.is_none()
+            && sale.ft_token_id.is_none() && sale.reserved_for.is_none()
+        {
+            Some(sale.sale_conditions.0)
+        } else {
+            None
+        }
+    }
+
+    //internal method for adding a sale to the price index of its nft contract and of its seller
+    pub(crate) fn internal_index_price(&mut self, sale: &Sale) {
+        let price = if let Some(price) = Self::indexed_price(sale) {
+            price
+        } else {
+            return;
+        };
+        let nft_contract_id: AccountId = sale.nft_contract_id.parse().unwrap();
+
+        //get the price index for the nft contract. If there is none, we create a new empty tree
+        let mut by_price = self.by_price.get(&nft_contract_id).unwrap_or_else(|| {
+            TreeMap::new(
+                StorageKey::ByPriceInner {
+                    //we get a new unique prefix for the collection by hashing the nft contract
+                    account_id_hash: hash_account_id(&nft_contract_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_price.insert(&(price, sale.token_id.clone()), &());
+        self.by_price.insert(&nft_contract_id, &by_price);
+
+        //get the price index for the seller. If there is none, we create a new empty tree
+        let mut by_seller_price = self.by_price_by_owner_id.get(&sale.owner_id).unwrap_or_else(|| {
+            TreeMap::new(
+                StorageKey::ByPriceByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&sale.owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_seller_price.insert(&(price, format!("{}{}{}", sale.nft_contract_id, DELIMITER, sale.token_id)), &());
+        self.by_price_by_owner_id.insert(&sale.owner_id, &by_seller_price);
+    }
+
+    //internal method for removing a sale from the price index of its nft contract and of its seller
+    pub(crate) fn internal_unindex_price(&mut self, sale: &Sale) {
+        let price = if let Some(price) = Self::indexed_price(sale) {
+            price
+        } else {
+            return;
+        };
+        let nft_contract_id: AccountId = sale.nft_contract_id.parse().unwrap();
+
+        if let Some(mut by_price) = self.by_price.get(&nft_contract_id) {
+            by_price.remove(&(price, sale.token_id.clone()));
+            //if the tree is now empty, we remove that nft contract ID from the map
+            if by_price.is_empty() {
+                self.by_price.remove(&nft_contract_id);
+            } else {
+                self.by_price.insert(&nft_contract_id, &by_price);
+            }
+        }
+
+        if let Some(mut by_seller_price) = self.by_price_by_owner_id.get(&sale.owner_id) {
+            by_seller_price.remove(&(price, format!("{}{}{}", sale.nft_contract_id, DELIMITER, sale.token_id)));
+            //if the tree is now empty, we remove that seller from the map
+            if by_seller_price.is_empty() {
+                self.by_price_by_owner_id.remove(&sale.owner_id);
+            } else {
+                self.by_price_by_owner_id.insert(&sale.owner_id, &by_seller_price);
+            }
+        }
+    }
+
+    //internal method for taking down a sale that won't be purchased. If the sale is an auction, the highest bidder is refunded
+    pub(crate) fn internal_cancel_sale(&mut self, nft_contract_id: AccountId, token_id: TokenId, reason: &str) -> Sale {
+        //get the sale object by removing the sale
+        let sale = self.internal_remove_sale(nft_contract_id, token_id);
+
+        //refund the bidder whose funds the market was holding
+        if let Some(bid) = sale.auction.as_ref().and_then(|auction| auction.highest_bid.as_ref()) {
+            Promise::new(bid.bidder_id.clone()).transfer(bid.amount.0);
+        }
+
+        // Construct the delist log as per the events standard.
+        let sale_delist_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SaleDelist(vec![SaleDelistLog {
+                owner_id: sale.owner_id.to_string(),
+                nft_contract_id: sale.nft_contract_id.clone(),
+                token_id: sale.token_id.clone(),
+                // Why the sale was taken down.
+                reason: reason.to_string(),
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&sale_delist_log.to_string());
+
+        sale
+    }
+
+    /*
+        internal method for purchasing a token through the nft contract. The payout for the price minus the marketplace fee is
+        queried with nft_payout first so that resolve_purchase_payout can check it against the payout policy before the token
+        is transferred. Only then is the token transferred and resolve_purchase distributes the funds (or refunds the buyer).
+    */
+    pub(crate) fn internal_transfer_payout(&self, context: PurchaseContext, price: U128, buyer_id: AccountId) -> Promise {
+        //get the payout without transferring the token
+        self.internal_nft_payout(&context, price)
+        //after the payout has been returned, we check it by calling our own resolve_purchase_payout function. If it's valid,
+        //that function transfers the token and passes the payout on to resolve_purchase
+        .then(
+            // No attached deposit with static GAS equal to the GAS for checking the payout, transferring the token and resolving the purchase.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_PURCHASE_PAYOUT + GAS_FOR_NFT_TRANSFER + context.gas_for_resolve_purchase())
+            .resolve_purchase_payout(
+                buyer_id, //the buyer and price are passed in incase something goes wrong and we need to refund the buyer
+                price,
+                context, //the rest of the purchase is passed in so the payout can be checked and the sale can be recorded
+            )
+        )
+    }
+
+    //internal method for querying the payout of a purchase from the nft contract without transferring the token
+    pub(crate) fn internal_nft_payout(&self, context: &PurchaseContext, price: U128) -> Promise {
+        ext_contract::ext(context.nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_PAYOUT)
+            .nft_payout(
+                context.token_id.clone(), //token ID being purchased
+                /*
+                    the price that the token was purchased for minus the marketplace fee. This will be used in conjunction with the
+                    royalty percentages for the token in order to determine how much money should go to which account. 
+                */
+                U128(price.0 - context.fee.0),
+                MAX_LEN_PAYOUT, //the maximum amount of accounts the market can payout at once (this is limited by GAS)
+            )
+    }
+
+    //internal method for transferring the token of a purchase whose payout has been checked. The nft contract returns the payout again
+    pub(crate) fn internal_nft_transfer_payout(&self, context: &PurchaseContext, buyer_id: AccountId, price: U128) -> Promise {
+        ext_contract::ext(context.nft_contract_id.clone())
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer_payout(
+                buyer_id, //purchaser (person to transfer the NFT to)
+                context.token_id.clone(), //token ID to transfer
+                context.approval_id, //market contract's approval ID in order to transfer the token on behalf of the owner
+                "payout from market".to_string(), //memo (to include some context)
+                U128(price.0 - context.fee.0), //the price minus the marketplace fee, the same balance the payout was checked for
+                MAX_LEN_PAYOUT, //the maximum amount of accounts the market can payout at once (this is limited by GAS)
+            )
+    }
+}
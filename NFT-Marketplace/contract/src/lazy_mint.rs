@@ -0,0 +1,257 @@
+use crate::*;
+use near_sdk::{is_promise_success, promise_result_as_success};
+
+//the parts of the series returned by get_series_details that the market needs to list it
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSeries {
+    pub owner_id: AccountId,
+    pub price: Option<U128>,
+}
+
+//struct that holds a series whose editions are minted to the buyer when they're purchased on the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LazyMintListing {
+    pub owner_id: AccountId,                        //owner of the series
+    pub nft_contract_id: AccountId,                 //nft contract the series was created on
+    pub series_id: U64,                             //ID of the series on the nft contract
+    pub price: U128,                                //price in yoctoNEAR of every edition sold through the market
+    pub remaining: Option<u64>,                     //editions that can still be sold through the market. If None, until the series runs out
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        list the editions of a series for sale. Every purchase mints a new edition to the buyer by calling nft_mint on the
+        series contract with the price minus the marketplace fee attached, which the series contract pays out to the series owner.
+        Only series with a price can be minted by the market and the caller must own the series, which is checked with the nft contract.
+    */
+    pub fn list_lazy_mint(&mut self, nft_contract_id: AccountId, series_id: U64, price: U128, editions: Option<u64>) -> Promise {
+        //make sure the marketplace isn't paused and the nft contract can be traded here
+        self.assert_not_paused();
+        self.assert_nft_contract_allowed(&nft_contract_id);
+
+        let owner_id = env::predecessor_account_id();
+        //we need to enforce that the user has enough storage for 1 EXTRA entry
+        self.assert_storage_available(&owner_id);
+        assert!(editions.is_none_or(|editions| editions > 0), "Must list at least 1 edition");
+
+        //query the nft contract for the series and check it against the caller in the callback
+        ext_contract::ext(nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_TOKEN)
+            .get_series_details(series_id.0)
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_LIST_LAZY_MINT)
+            .resolve_list_lazy_mint(LazyMintListing { owner_id, nft_contract_id, series_id, price, remaining: editions })
+        )
+    }
+
+    //removes a lazy mint listing from the market
+    #[payable]
+    pub fn remove_lazy_mint(&mut self, nft_contract_id: AccountId, series_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        let listing = self.internal_remove_lazy_mint(&nft_contract_id, series_id);
+        //make sure the caller is the owner of the listing. If this fails, the removal will revert
+        assert_eq!(env::predecessor_account_id(), listing.owner_id, "Must be listing owner");
+    }
+
+    //purchase a new edition of a listed series. The edition is minted to the caller and any overpayment is refunded
+    #[payable]
+    pub fn offer_lazy_mint(&mut self, nft_contract_id: AccountId, series_id: U64) -> Promise {
+        //make sure the marketplace isn't paused
+        self.assert_not_paused();
+        let deposit = env::attached_deposit();
+        let buyer_id = env::predecessor_account_id();
+
+        let listing_id = format!("{}{}{}", nft_contract_id, DELIMITER, series_id.0);
+        let mut listing = self.lazy_mints.get(&listing_id).expect("No lazy mint listing");
+        assert_ne!(listing.owner_id, buyer_id, "Cannot buy from your own series");
+        assert!(deposit >= listing.price.0, "Attached deposit must be greater than or equal to the current price: {:?}", listing.price);
+
+        //one less edition can be sold. Once they're all sold, the listing is taken down
+        let price = listing.price.0;
+        let owner_id = listing.owner_id.clone();
+        match listing.remaining.as_mut() {
+            Some(1) => {
+                self.internal_remove_lazy_mint(&nft_contract_id, series_id);
+            }
+            Some(remaining) => {
+                *remaining -= 1;
+                self.lazy_mints.insert(&listing_id, &listing);
+            }
+            None => {}
+        }
+
+        //refund the part of the deposit that isn't needed for the edition
+        if deposit > price {
+            Promise::new(buyer_id.clone()).transfer(deposit - price);
+        }
+
+        //mint the edition to the buyer. The price minus the marketplace fee is paid to the series owner by the series contract
+        let fee = self.internal_fee_for(price);
+        ext_contract::ext(nft_contract_id.clone())
+            .with_attached_deposit(price - fee)
+            .with_static_gas(GAS_FOR_NFT_MINT)
+            .nft_mint(series_id, buyer_id.clone())
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_LAZY_MINT)
+            .resolve_lazy_mint(nft_contract_id, series_id, owner_id, buyer_id, U128(price), U128(fee))
+        )
+    }
+
+    //private method used to resolve the promise when calling get_series_details. Returns true if the series was listed
+    #[private]
+    pub fn resolve_list_lazy_mint(&mut self, listing: LazyMintListing) -> bool {
+        //get the series returned by the nft contract. If the call failed or there's no series, we don't list it
+        let series = promise_result_as_success()
+            .and_then(|value| near_sdk::serde_json::from_slice::<Option<JsonSeries>>(&value).ok())
+            .flatten();
+        let series = if let Some(series) = series {
+            series
+        } else {
+            env::log_str("Could not get the series from the nft contract");
+            return false;
+        };
+
+        //only the series owner can list it and the series contract only lets the market mint series with a price
+        assert_eq!(series.owner_id, listing.owner_id, "Must be series owner");
+        let series_price = series.price.expect("Series must have a price to be minted through the market");
+        assert!(
+            listing.price.0 - self.internal_fee_for(listing.price.0) > series_price.0,
+            "Price minus the marketplace fee must be greater than the series price: {:?}", series_price
+        );
+        //the storage is checked again since the account could have used it while the series was being queried
+        self.assert_storage_available(&listing.owner_id);
+
+        let listing_id = format!("{}{}{}", listing.nft_contract_id, DELIMITER, listing.series_id.0);
+        //a series that was already listed is replaced so the owner can change its price or editions
+        if self.lazy_mints.get(&listing_id).is_some() {
+            self.internal_remove_lazy_mint(&listing.nft_contract_id, listing.series_id);
+        }
+
+        //get the lazy mint listings for the owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.lazy_mints_by_owner_id.get(&listing.owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::LazyMintsByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&listing.owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_owner_id.insert(&listing_id);
+        self.lazy_mints_by_owner_id.insert(&listing.owner_id, &by_owner_id);
+        self.lazy_mints.insert(&listing_id, &listing);
+        //update the listing stats for the nft contract and the series owner
+        self.internal_record_listing(&listing.nft_contract_id, &listing.owner_id);
+        true
+    }
+
+    /*
+        private method used to resolve the promise when calling nft_mint. If the edition was minted, the marketplace fee is
+        added to the treasury. If it wasn't, the attached deposit came back to the market and the buyer is refunded in full.
+    */
+    #[private]
+    pub fn resolve_lazy_mint(
+        &mut self,
+        nft_contract_id: AccountId,
+        series_id: U64,
+        owner_id: AccountId,
+        buyer_id: AccountId,
+        price: U128,
+        fee: U128,
+    ) -> bool {
+        if !is_promise_success() {
+            env::log_str(&format!("Could not mint an edition of series {} on {}", series_id.0, nft_contract_id));
+            Promise::new(buyer_id).transfer(price.0);
+
+            //the edition wasn't sold so it can be sold again if the series is still listed
+            let listing_id = format!("{}{}{}", nft_contract_id, DELIMITER, series_id.0);
+            if let Some(mut listing) = self.lazy_mints.get(&listing_id) {
+                if let Some(remaining) = listing.remaining.as_mut() {
+                    *remaining += 1;
+                    self.lazy_mints.insert(&listing_id, &listing);
+                }
+            }
+            return false;
+        }
+
+        //add the fee to the treasury and record the sale's volume for the nft contract
+        self.internal_record_sale(&nft_contract_id, None, price.0, fee.0);
+        //update the sales stats for the nft contract and the series owner
+        self.internal_record_sale_stats(&nft_contract_id, &owner_id, None, price.0);
+
+        // Construct the lazy mint log as per the events standard.
+        let sale_lazy_mint_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SaleLazyMint(vec![SaleLazyMintLog {
+                owner_id: owner_id.to_string(),
+                buyer_id: buyer_id.to_string(),
+                nft_contract_id: nft_contract_id.to_string(),
+                series_id,
+                price,
+                fee,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&sale_lazy_mint_log.to_string());
+        true
+    }
+
+    // views
+    //returns the lazy mint listing for a given series
+    pub fn get_lazy_mint(&self, nft_contract_id: AccountId, series_id: U64) -> Option<LazyMintListing> {
+        self.lazy_mints.get(&format!("{}{}{}", nft_contract_id, DELIMITER, series_id.0))
+    }
+
+    //returns paginated lazy mint listings for a given account
+    pub fn get_lazy_mints_by_owner_id(&self, account_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<LazyMintListing> {
+        //get the set of listing IDs for the given account. If there wasn't a set, return an empty vector
+        let by_owner_id = if let Some(by_owner_id) = self.lazy_mints_by_owner_id.get(&account_id) {
+            by_owner_id
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_owner_id.iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 0
+            .take(limit.unwrap_or(0) as usize)
+            //we'll map the listing IDs into listing objects
+            .map(|listing_id| self.lazy_mints.get(&listing_id).unwrap())
+            .collect()
+    }
+}
+
+impl Contract {
+    //internal method for removing a lazy mint listing from the market. This returns the previously removed listing object
+    pub(crate) fn internal_remove_lazy_mint(&mut self, nft_contract_id: &AccountId, series_id: U64) -> LazyMintListing {
+        let listing_id = format!("{}{}{}", nft_contract_id, DELIMITER, series_id.0);
+        let listing = self.lazy_mints.remove(&listing_id).expect("No lazy mint listing");
+
+        //remove the listing ID from the owner's set and remove the set if it's now empty
+        let mut by_owner_id = self.lazy_mints_by_owner_id.get(&listing.owner_id).expect("No lazy mint listing by owner_id");
+        by_owner_id.remove(&listing_id);
+        if by_owner_id.is_empty() {
+            self.lazy_mints_by_owner_id.remove(&listing.owner_id);
+        } else {
+            self.lazy_mints_by_owner_id.insert(&listing.owner_id, &by_owner_id);
+        }
+
+        listing
+    }
+}
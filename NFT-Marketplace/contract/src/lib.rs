@@ -1,26 +1,89 @@
 use near_sdk::borsh::{ self, BorshDeserialize, BorshSerialize };
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::collections::{ LookupMap, UnorderedMap, UnorderedSet };
+use near_sdk::collections::{ LookupMap, TreeMap, UnorderedMap, UnorderedSet };
 use near_sdk::json_types::{ U128, U64 };
-use near_sdk::{ assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, Promise, CryptoHash, BorshStorageKey };
+use near_sdk::{ assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, CryptoHash, BorshStorageKey };
 use near_sdk::env::STORAGE_PRICE_PER_BYTE;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
+mod auction;
+mod batch;
+mod bundle;
+mod buy_offers;
+mod dutch_auction;
+mod events;
 mod external;
+mod fees;
+mod ft_callbacks;
+mod history;
 mod internal;
+mod lazy_mint;
+mod migrate;
+mod moderation;
 mod nft_callbacks;
+mod payout_policy;
+mod prune;
 mod sale;
 mod sale_reviews;
+mod stats;
+mod storage;
+mod swap;
+mod update;
 
+use crate::auction::*;
+use crate::batch::*;
+use crate::bundle::*;
+use crate::buy_offers::*;
+use crate::dutch_auction::*;
+use crate::events::*;
 use crate::external::*;
+use crate::fees::*;
+use crate::history::*;
 use crate::internal::*;
+use crate::lazy_mint::*;
 use crate::sale::*;
+use crate::stats::*;
+use crate::swap::*;
 
 //GAS constants to attach to calls
 const GAS_FOR_RESOLVE_PURCHASE: Gas = Gas(115_000_000_000_000);
+//NEAR payouts are plain transfers instead of ft_transfer calls so resolving them needs a lot less GAS
+const GAS_FOR_RESOLVE_NEAR_PURCHASE: Gas = Gas(25_000_000_000_000);
 const GAS_FOR_NFT_TRANSFER: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_WITHDRAW_FEES: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_NFT_TOKEN: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_PRUNE_SALE: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_NFT_PAYOUT: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_PURCHASE_PAYOUT: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_ROYALTY_SCHEDULE: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_SWAP: Gas = Gas(35_000_000_000_000);
+const GAS_FOR_RESOLVE_SWAP_ESCROW: Gas = Gas(65_000_000_000_000);
+const GAS_FOR_RESOLVE_ESCROW_RELEASE: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_NFT_MINT: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_RESOLVE_LAZY_MINT: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_RESOLVE_LIST_LAZY_MINT: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_MIGRATE: Gas = Gas(200_000_000_000_000);
+//migrate doesn't take any arguments
+const NO_ARGS: Vec<u8> = vec![];
 //the minimum storage to have a sale on the contract.
 const STORAGE_PER_SALE: u128 = 1000 * STORAGE_PRICE_PER_BYTE;
+//the maximum amount of accounts the market can payout at once (this is limited by GAS)
+const MAX_LEN_PAYOUT: u32 = 10;
+//the maximum number of sales that can be updated or removed in one call
+const MAX_BATCH_SIZE: usize = 50;
+//the maximum number of the cheapest sales looked at when finding a floor price (this is limited by GAS). Sales that
+//have expired can be cleared out of the way with remove_expired_sales
+const MAX_FLOOR_SCAN: usize = 50;
+//the standard name and version used for the marketplace's events
+const MARKET_STANDARD_NAME: &str = "nft_market";
+const MARKET_EVENTS_VERSION: &str = "1.0.0";
+//the maximum number of tokens in a bundle (this is limited by GAS since every token is transferred in the same purchase)
+const MAX_BUNDLE_ITEMS: usize = 4;
+//the maximum number of accounts a private sale can be reserved for
+const MAX_RESERVED_FOR: usize = 10;
+//the number of recent sales kept on chain for every nft contract
+const SALE_HISTORY_LENGTH: usize = 20;
 //every sale will have a unique ID which is `CONTRACT + DELIMITER + TOKEN_ID`
 const DELIMITER: &str = ".";
 
@@ -54,7 +117,67 @@ pub struct Contract {
     //keep track of all the token IDs for sale for a given contract
     pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
     //keep track of the storage that accounts have payed
-    pub storage_deposits: LookupMap<AccountId, Balance>
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    //keep track of the fungible tokens that sales can be priced in
+    pub approved_ft_token_ids: UnorderedSet<FungibleTokenId>,
+    //keep track of the standing buy offers by their unique offer ID
+    pub buy_offers: UnorderedMap<BuyOfferId, BuyOffer>,
+    //keep track of all the buy offer IDs for every buyer
+    pub buy_offers_by_buyer_id: LookupMap<AccountId, UnorderedSet<BuyOfferId>>,
+    //keep track of all the buy offer IDs (token and collection offers) for a given contract
+    pub buy_offers_by_nft_contract_id: LookupMap<AccountId, UnorderedSet<BuyOfferId>>,
+    //the ID that will be given to the next buy offer
+    pub next_buy_offer_id: BuyOfferId,
+    //the fee (in basis points) the marketplace takes from every sale
+    pub fee_basis_points: u32,
+    //accumulated NEAR fees that the owner hasn't withdrawn yet
+    pub near_fee_balance: Balance,
+    //accumulated fees for every fungible token that the owner hasn't withdrawn yet
+    pub ft_fee_balances: LookupMap<FungibleTokenId, Balance>,
+    //keep track of the lifetime volume and fees for every nft contract
+    pub volume_by_nft_contract_id: LookupMap<AccountId, CollectionVolume>,
+    //keep the fixed price NEAR sales for a given contract ordered by price (and token ID to break ties)
+    pub by_price: LookupMap<AccountId, TreeMap<(Balance, TokenId), ()>>,
+    //keep track of the most recent completed sales for every nft contract (oldest first)
+    pub sale_history: LookupMap<AccountId, VecDeque<SaleRecord>>,
+    //if true, no new sales or buy offers can be created and nothing can be purchased
+    pub paused: bool,
+    //if true, only nft contracts on the allowlist can be listed
+    pub allowlist_enabled: bool,
+    //nft contracts that can be listed when the allowlist is enabled
+    pub allowed_nft_contract_ids: UnorderedSet<AccountId>,
+    //nft contracts that can never be listed
+    pub denied_nft_contract_ids: UnorderedSet<AccountId>,
+    //nft contracts that the owner verified as the real collection
+    pub verified_nft_contract_ids: UnorderedSet<AccountId>,
+    //the most (in basis points) that the royalty accounts together can receive from a sale
+    pub max_royalty_basis_points: u32,
+    //the least (in basis points) that the seller must receive from a sale
+    pub min_seller_basis_points: u32,
+    //the royalty schedule (in basis points for every royalty account) that payouts of verified nft contracts are checked against
+    pub royalty_schedules: LookupMap<AccountId, HashMap<AccountId, u32>>,
+    //keep track of all the private sale IDs that every account can purchase
+    pub by_reserved_for: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    //keep track of the bundles by their unique bundle ID
+    pub bundles: UnorderedMap<BundleId, Bundle>,
+    //keep track of all the bundle IDs for every account ID
+    pub bundles_by_owner_id: LookupMap<AccountId, UnorderedSet<BundleId>>,
+    //keep track of the bundle every bundled token is in
+    pub bundle_by_token: LookupMap<ContractAndTokenId, BundleId>,
+    //the ID that will be given to the next bundle
+    pub next_bundle_id: BundleId,
+    //keep track of the series listed for lazy minting by their unique listing ID (contract + DELIMITER + series ID)
+    pub lazy_mints: UnorderedMap<String, LazyMintListing>,
+    //keep track of all the lazy mint listing IDs for every series owner
+    pub lazy_mints_by_owner_id: LookupMap<AccountId, UnorderedSet<String>>,
+    //keep track of the listing and sales stats for every nft contract
+    pub stats_by_nft_contract_id: LookupMap<AccountId, MarketStats>,
+    //keep track of the listing and sales stats for every seller
+    pub stats_by_seller_id: LookupMap<AccountId, MarketStats>,
+    //keep track of the swapped tokens the market still holds because sending them out of escrow failed, and who can claim them
+    pub escrowed_tokens: UnorderedMap<ContractAndTokenId, AccountId>,
+    //keep track of every seller's public fixed price NEAR sales ordered by price so their floor can be found without going through all their sales
+    pub by_price_by_owner_id: LookupMap<AccountId, TreeMap<(Balance, ContractAndTokenId), ()>>
 }
 
 // Helper structure to for keys of the persistent collections
@@ -68,7 +191,35 @@ pub enum StorageKey {
     ByNFTTokenType,
     ByNFTTokenTypeInner { token_type_hash: CryptoHash },
     FTTokenIds,
-    StorageDeposits
+    StorageDeposits,
+    BuyOffers,
+    BuyOffersByBuyerId,
+    BuyOffersByBuyerIdInner { account_id_hash: CryptoHash },
+    BuyOffersByNFTContractId,
+    BuyOffersByNFTContractIdInner { account_id_hash: CryptoHash },
+    FTFeeBalances,
+    VolumeByNFTContractId,
+    ByPrice,
+    ByPriceInner { account_id_hash: CryptoHash },
+    SaleHistory,
+    AllowedNFTContractIds,
+    DeniedNFTContractIds,
+    VerifiedNFTContractIds,
+    RoyaltySchedules,
+    ByReservedFor,
+    ByReservedForInner { account_id_hash: CryptoHash },
+    Bundles,
+    BundlesByOwnerId,
+    BundlesByOwnerIdInner { account_id_hash: CryptoHash },
+    BundleByToken,
+    LazyMints,
+    LazyMintsByOwnerId,
+    LazyMintsByOwnerIdInner { account_id_hash: CryptoHash },
+    StatsByNFTContractId,
+    StatsBySellerId,
+    EscrowedTokens,
+    ByPriceByOwnerId,
+    ByPriceByOwnerIdInner { account_id_hash: CryptoHash }
 }
 
 #[near_bindgen]
@@ -80,84 +231,1306 @@ impl Contract {
     */
     #[init]
     pub fn new(owner_id: AccountId) -> Self {
+        //mark the state with the current layout so future code knows how to migrate it
+        Self::internal_write_state_version();
         Self { 
             owner_id, 
             sales: UnorderedMap::new(StorageKey::Sales), 
             by_owner_id: LookupMap::new(StorageKey::ByOwnerId), 
             by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId), 
-            storage_deposits: LookupMap::new(StorageKey::StorageDeposits) 
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            approved_ft_token_ids: UnorderedSet::new(StorageKey::FTTokenIds),
+            buy_offers: UnorderedMap::new(StorageKey::BuyOffers),
+            buy_offers_by_buyer_id: LookupMap::new(StorageKey::BuyOffersByBuyerId),
+            buy_offers_by_nft_contract_id: LookupMap::new(StorageKey::BuyOffersByNFTContractId),
+            next_buy_offer_id: 0,
+            fee_basis_points: 0,
+            near_fee_balance: 0,
+            ft_fee_balances: LookupMap::new(StorageKey::FTFeeBalances),
+            volume_by_nft_contract_id: LookupMap::new(StorageKey::VolumeByNFTContractId),
+            by_price: LookupMap::new(StorageKey::ByPrice),
+            sale_history: LookupMap::new(StorageKey::SaleHistory),
+            paused: false,
+            allowlist_enabled: false,
+            allowed_nft_contract_ids: UnorderedSet::new(StorageKey::AllowedNFTContractIds),
+            denied_nft_contract_ids: UnorderedSet::new(StorageKey::DeniedNFTContractIds),
+            verified_nft_contract_ids: UnorderedSet::new(StorageKey::VerifiedNFTContractIds),
+            max_royalty_basis_points: 10_000,
+            min_seller_basis_points: 0,
+            royalty_schedules: LookupMap::new(StorageKey::RoyaltySchedules),
+            by_reserved_for: LookupMap::new(StorageKey::ByReservedFor),
+            bundles: UnorderedMap::new(StorageKey::Bundles),
+            bundles_by_owner_id: LookupMap::new(StorageKey::BundlesByOwnerId),
+            bundle_by_token: LookupMap::new(StorageKey::BundleByToken),
+            next_bundle_id: 0,
+            lazy_mints: UnorderedMap::new(StorageKey::LazyMints),
+            lazy_mints_by_owner_id: LookupMap::new(StorageKey::LazyMintsByOwnerId),
+            stats_by_nft_contract_id: LookupMap::new(StorageKey::StatsByNFTContractId),
+            stats_by_seller_id: LookupMap::new(StorageKey::StatsBySellerId),
+            escrowed_tokens: UnorderedMap::new(StorageKey::EscrowedTokens),
+            by_price_by_owner_id: LookupMap::new(StorageKey::ByPriceByOwnerId)
         }
     }
 
-    //Allows users to deposit storage. This is to cover the cost of storing sale objects on the contract
-    //Optional account ID is to users can pay for storage for other people.
-    #[payable]
-    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) {
-        // get the account ID to pay for storage for
-        let storage_account_id = account_id 
-            //convert the valid account ID into an account ID
-            .map(|a| a.into())
-            //if we didn't specify an account ID, we simply use the caller of the function
-            .unwrap_or_else(env::predecessor_account_id);
-
-        //get the deposit value which is how much the user wants to add to their storage
-        let deposit = env::attached_deposit();
-
-        //make sure the deposit is greater than or equal to the minimum storage for a sale
-        assert!(deposit >= STORAGE_PER_SALE, "Require minimum deposit of {}", STORAGE_PER_SALE);
-
-        //get the balance of the account (if the account isn't in the map we default to a balance of 0)
-        let mut balance: u128 = self.storage_deposits.get(&storage_account_id).unwrap_or(0);
-        //add the deposit to their balance
-        balance += deposit;
-        //insert the balance back into the map for that account ID
-        self.storage_deposits.insert(&storage_account_id, &balance);   
-    }
-
-    //Allows users to withdraw any excess storage that they're not using. Say Bob pays 0.01N for 1 sale
-    //Alice then buys Bob's token. This means bob has paid 0.01N for a sale that's no longer on the marketplace
-    //Bob could then withdraw this 0.01N back into his account.
-    #[payable]
-    pub fn storage_withdraw(&mut self) {
-        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
-        //this will redirect them to the NEAR wallet (or requires a full access key). 
-        assert_one_yocto();
-
-        //the account to withdraw storage to is always the function caller
-        let owner_id = env::predecessor_account_id();
-        //get the amount that the user has by removing them from the map. If they're not in the map, default to 0
-        let mut amount = self.storage_deposits.remove(&owner_id).unwrap_or(0);
-
-        //how many sales is that user taking up currently. This returns a set
-        let sales = self.by_owner_id.get(&owner_id);
-        //get the length of that set. 
-        let len = sales.map(|s| s.len()).unwrap_or_default();
-        //how much NEAR is being used up for all the current sales on the account 
-        let diff = u128::from(len)*STORAGE_PER_SALE;
-
-        //the excess to withdraw is the total storage paid - storage being used up.
-        amount -= diff;
-
-        //if that excess to withdraw is > 0, we transfer the amount to the user.
-        if amount > 0 {
-            Promise::new(owner_id.clone()).transfer(amount);
+    //Allows the owner to add fungible tokens that sales can be priced in
+    pub fn add_ft_token_ids(&mut self, ft_token_ids: Vec<FungibleTokenId>) {
+        //make sure only the owner can change the whitelist
+        self.assert_owner();
+        for ft_token_id in ft_token_ids {
+            self.approved_ft_token_ids.insert(&ft_token_id);
         }
-        //we need to add back the storage being used up into the map if it's greater than 0.
-        //this is so that if the user had 500 sales on the market, we insert that value here so
-        //if those sales get taken down, the user can then go and withdraw 500 sales worth of storage.
-        if diff > 0 {
-            self.storage_deposits.insert(&owner_id, &diff);
+    }
+
+    //Allows the owner to remove fungible tokens from the whitelist. Existing sales in that token can no longer be purchased
+    pub fn remove_ft_token_ids(&mut self, ft_token_ids: Vec<FungibleTokenId>) {
+        //make sure only the owner can change the whitelist
+        self.assert_owner();
+        for ft_token_id in ft_token_ids {
+            self.approved_ft_token_ids.remove(&ft_token_id);
         }
     }
 
     // views
-    // return the minimum storage for 1 sale
-    pub fn storage_minimum_balance(&self) -> U128 {
-        U128(STORAGE_PER_SALE)
-    } 
+    // return the fungible tokens that sales can be priced in
+    pub fn get_ft_token_ids(&self) -> Vec<FungibleTokenId> {
+        self.approved_ft_token_ids.to_vec()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ft_callbacks::FungibleTokenReceiver;
+    use crate::migrate::{ContractV1, ContractV2, SaleV1};
+    use crate::nft_callbacks::NonFungibleTokenApprovalsReceiver;
+    use crate::storage::StorageManagement;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+    const MARKET: &str = "market.near";
+    const NFT_CONTRACT: &str = "nft.near";
+    const FT_CONTRACT: &str = "usdc.near";
+    const SELLER: &str = "seller.near";
+    const NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+    const MINUTE: u64 = 60 * 1_000_000_000;
+
+    fn set_context(predecessor: &str, signer: &str, deposit: Balance, timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(MARKET.parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .signer_account_id(signer.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(timestamp)
+            .build());
+    }
+
+    //sets the context for a callback on the marketplace that receives the given promise results
+    fn set_promise_results(results: Vec<PromiseResult>) {
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(MARKET.parse().unwrap())
+                .predecessor_account_id(MARKET.parse().unwrap())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            results,
+        );
+    }
+
+    //sets the context for a callback on the marketplace that receives the given promise result
+    fn set_promise_result(result: &str) {
+        set_promise_results(vec![PromiseResult::Successful(result.as_bytes().to_vec())]);
+    }
+
+    //the details of a purchase of one of the seller's tokens
+    fn purchase_context(token_id: &str, fee: u128) -> PurchaseContext {
+        PurchaseContext {
+            nft_contract_id: NFT_CONTRACT.parse().unwrap(),
+            token_id: token_id.to_string(),
+            approval_id: 0,
+            seller_id: SELLER.parse().unwrap(),
+            fee: U128(fee),
+            ft_token_id: None,
+        }
+    }
+
+    //returns true if the payout returned by nft_payout is accepted and the token is transferred to the buyer
+    fn payout_accepted(contract: &mut Contract, price: u128, fee: u128, payout: &str) -> bool {
+        set_promise_result(payout);
+        matches!(
+            contract.resolve_purchase_payout("alice.near".parse().unwrap(), U128(price), purchase_context("1", fee)),
+            PromiseOrValue::Promise(_)
+        )
+    }
+
+    //resolves the purchase of a token that was transferred after the given payout was checked
+    fn resolve_transferred(contract: &mut Contract, token_id: &str, price: u128, fee: u128, payout: &str) -> U128 {
+        set_promise_result(payout);
+        let payout: Payout = near_sdk::serde_json::from_str(payout).unwrap();
+        contract.resolve_purchase(
+            "alice.near".parse().unwrap(),
+            CheckedPurchase { price: U128(price), payout: payout.payout, context: purchase_context(token_id, fee) },
+        )
+    }
+
+    //creates a marketplace with a single token listed by the seller using the given msg
+    fn setup_sale(msg: &str) -> Contract {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        set_context(MARKET, MARKET, 0, 0);
+        contract.add_ft_token_ids(vec![FT_CONTRACT.parse().unwrap()]);
+        set_context(SELLER, SELLER, STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("1".to_string(), SELLER.parse().unwrap(), 0, msg.to_string());
+        contract
+    }
+
+    fn setup_auction() -> Contract {
+        setup_sale(&format!(
+            r#"{{"sale_conditions":"{}","auction":{{"starts_at":"0","ends_at":"{}","reserve_price":"{}","min_bid_increment":"{}"}}}}"#,
+            NEAR, 60 * MINUTE, 5 * NEAR, NEAR
+        ))
+    }
+
+    fn get_auction(contract: &Contract) -> Auction {
+        contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).unwrap().auction.unwrap()
+    }
+
+    #[test]
+    fn auction_bids_replace_highest_bid() {
+        let mut contract = setup_auction();
+
+        set_context("alice.near", "alice.near", NEAR, MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        set_context("bob.near", "bob.near", 2 * NEAR, 2 * MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+
+        let bid = get_auction(&contract).highest_bid.unwrap();
+        assert_eq!(bid.bidder_id, "bob.near".parse::<AccountId>().unwrap());
+        assert_eq!(bid.amount.0, 2 * NEAR);
+        //the sale stays listed until the auction is settled
+        assert_eq!(contract.get_supply_sales().0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid must be greater than or equal to")]
+    fn auction_rejects_bid_below_increment() {
+        let mut contract = setup_auction();
+
+        set_context("alice.near", "alice.near", NEAR, MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        set_context("bob.near", "bob.near", NEAR + 1, 2 * MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    fn auction_bid_in_last_minutes_extends_end() {
+        let mut contract = setup_auction();
+
+        set_context("alice.near", "alice.near", NEAR, 55 * MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+
+        assert_eq!(get_auction(&contract).ends_at.0, 65 * MINUTE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has not ended")]
+    fn auction_cannot_settle_before_end() {
+        let mut contract = setup_auction();
+
+        set_context("alice.near", "alice.near", 0, 30 * MINUTE);
+        contract.settle_auction(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    fn auction_below_reserve_is_removed_on_settle() {
+        let mut contract = setup_auction();
+
+        set_context("alice.near", "alice.near", NEAR, MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        set_context("alice.near", "alice.near", 0, 61 * MINUTE);
+        contract.settle_auction(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    fn ft_purchase_returns_excess_amount() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"100","ft_token_id":"{}"}}"#, FT_CONTRACT));
+
+        set_context(FT_CONTRACT, "alice.near", 0, 0);
+        let unused = contract.ft_on_transfer(
+            "alice.near".parse().unwrap(),
+            U128(150),
+            format!(r#"{{"nft_contract_id":"{}","token_id":"1"}}"#, NFT_CONTRACT),
+        );
+
+        assert!(matches!(unused, PromiseOrValue::Value(U128(50))));
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "purchase it with ft_transfer_call")]
+    fn ft_sale_rejects_near_offer() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"100","ft_token_id":"{}"}}"#, FT_CONTRACT));
+
+        set_context("alice.near", "alice.near", NEAR, 0);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "FT token is not approved")]
+    fn listing_rejects_unapproved_ft() {
+        setup_sale(r#"{"sale_conditions":"100","ft_token_id":"scam.near"}"#);
+    }
+
+    //places a buy offer from alice after paying her storage
+    fn place_buy_offer(contract: &mut Contract, token_id: Option<&str>, expires_at: Option<u64>) -> U64 {
+        set_context("alice.near", "alice.near", STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        set_context("alice.near", "alice.near", NEAR, 0);
+        contract.place_buy_offer(NFT_CONTRACT.parse().unwrap(), token_id.map(|id| id.to_string()), expires_at.map(U64))
+    }
+
+    #[test]
+    fn buy_offers_for_token_are_paginated() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, 2 * NEAR));
+        let collection_offer = place_buy_offer(&mut contract, None, None);
+        place_buy_offer(&mut contract, Some("2"), None);
+        let token_offer = place_buy_offer(&mut contract, Some("1"), None);
+        assert_eq!(contract.get_supply_buy_offers_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()), U64(3));
+
+        let offer_ids = |from_index: u128, limit: u64| -> Vec<U64> {
+            contract
+                .get_buy_offers_for_token(NFT_CONTRACT.parse().unwrap(), "1".to_string(), Some(U128(from_index)), Some(limit))
+                .into_iter()
+                .map(|offer| offer.offer_id)
+                .collect()
+        };
+        //the offer for token 2 is skipped, so the second page is empty
+        assert_eq!(offer_ids(0, 1), vec![collection_offer]);
+        assert_eq!(offer_ids(1, 1), Vec::<U64>::new());
+        assert_eq!(offer_ids(2, 1), vec![token_offer]);
+        assert_eq!(offer_ids(0, 3), vec![collection_offer, token_offer]);
+    }
+
+    #[test]
+    fn accepting_buy_offer_removes_offer_and_listing() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, 2 * NEAR));
+        let offer_id = place_buy_offer(&mut contract, None, None);
+        assert_eq!(contract.get_buy_offers_for_token(NFT_CONTRACT.parse().unwrap(), "1".to_string(), None, Some(10)).len(), 1);
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("1".to_string(), SELLER.parse().unwrap(), 1, format!(r#"{{"accept_offer_id":"{}"}}"#, offer_id.0));
+
+        assert!(contract.get_buy_offer(offer_id).is_none());
+        assert_eq!(contract.get_supply_buy_offers_by_buyer_id("alice.near".parse().unwrap()).0, 0);
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Offer is for a different token")]
+    fn buy_offer_only_accepted_for_its_token() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, 2 * NEAR));
+        let offer_id = place_buy_offer(&mut contract, Some("2"), None);
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("1".to_string(), SELLER.parse().unwrap(), 1, format!(r#"{{"accept_offer_id":"{}"}}"#, offer_id.0));
+    }
+
+    #[test]
+    fn expired_buy_offer_can_be_cancelled_by_anyone() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, 2 * NEAR));
+        let offer_id = place_buy_offer(&mut contract, Some("1"), Some(MINUTE));
+
+        set_context("bob.near", "bob.near", 1, 2 * MINUTE);
+        contract.cancel_buy_offer(offer_id);
+
+        assert!(contract.get_buy_offer(offer_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Must be offer owner or offer must be expired")]
+    fn active_buy_offer_cannot_be_cancelled_by_others() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, 2 * NEAR));
+        let offer_id = place_buy_offer(&mut contract, Some("1"), None);
+
+        set_context("bob.near", "bob.near", 1, 0);
+        contract.cancel_buy_offer(offer_id);
+    }
+
+    #[test]
+    fn purchase_fee_is_added_to_treasury() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_fee_basis_points(250);
+
+        resolve_transferred(&mut contract, "1", 1000, 25, &format!(r#"{{"payout":{{"{}":"975"}}}}"#, SELLER));
+
+        assert_eq!(contract.get_fee_balance(None).0, 25);
+        let volume = contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap());
+        assert_eq!(volume.sales_count, 1);
+        assert_eq!(volume.near_volume.0, 1000);
+        assert_eq!(volume.near_fees.0, 25);
+
+        let history = contract.get_sale_history(NFT_CONTRACT.parse().unwrap());
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].token_id, "1");
+        assert_eq!(history[0].price.0, 1000);
+    }
+
+    #[test]
+    fn sale_history_keeps_most_recent_sales() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        for i in 0..SALE_HISTORY_LENGTH + 5 {
+            resolve_transferred(&mut contract, &i.to_string(), 1000, 0, &format!(r#"{{"payout":{{"{}":"1000"}}}}"#, SELLER));
+        }
+
+        //the oldest sales are dropped and the newest sale comes first
+        let history = contract.get_sale_history(NFT_CONTRACT.parse().unwrap());
+        assert_eq!(history.len(), SALE_HISTORY_LENGTH);
+        assert_eq!(history[0].token_id, (SALE_HISTORY_LENGTH + 4).to_string());
+        assert_eq!(history[SALE_HISTORY_LENGTH - 1].token_id, "5");
+    }
+
+    #[test]
+    fn stats_track_listings_and_sales() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        let stats = contract.get_stats_by_seller_id(SELLER.parse().unwrap());
+        assert_eq!(stats.listed_count.0, 1);
+        assert_eq!(stats.active_listings.0, 1);
+        assert_eq!(stats.floor_price, Some(U128(1000)));
+
+        //two tokens are sold, the highest price is kept separately from the last one
+        for (token_id, price) in [("1", 1000), ("2", 400)] {
+            resolve_transferred(&mut contract, token_id, price, 0, &format!(r#"{{"payout":{{"{}":"{}"}}}}"#, SELLER, price));
+        }
+
+        for stats in [
+            contract.get_stats_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()),
+            contract.get_stats_by_seller_id(SELLER.parse().unwrap()),
+        ] {
+            assert_eq!(stats.sold_count.0, 2);
+            assert_eq!(stats.volume.0, 1400);
+            assert_eq!(stats.last_sale_price, Some(U128(400)));
+            assert_eq!(stats.highest_sale_price, Some(U128(1000)));
+        }
+    }
+
+    #[test]
+    fn seller_floor_follows_price_changes_across_collections() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"300"}"#);
+        list_token(&mut contract, "2", 100);
+        set_context(SELLER, SELLER, STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        set_context("game.near", SELLER, 0, 0);
+        contract.nft_on_approve("7".to_string(), SELLER.parse().unwrap(), 0, r#"{"sale_conditions":"200"}"#.to_string());
+        let floor = |contract: &Contract| contract.get_stats_by_seller_id(SELLER.parse().unwrap()).floor_price;
+        assert_eq!(floor(&contract), Some(U128(100)));
+
+        //raising the cheapest price and taking the next sale down both move the floor
+        set_context(SELLER, SELLER, 1, 0);
+        contract.update_price(NFT_CONTRACT.parse().unwrap(), "2".to_string(), U128(500));
+        assert_eq!(floor(&contract), Some(U128(200)));
+        contract.remove_sale("game.near".parse().unwrap(), "7".to_string());
+        assert_eq!(floor(&contract), Some(U128(300)));
+
+        //once every sale is gone the seller has no floor left
+        contract.remove_sale(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        contract.remove_sale(NFT_CONTRACT.parse().unwrap(), "2".to_string());
+        assert_eq!(floor(&contract), None);
+        assert!(contract.by_price_by_owner_id.get(&SELLER.parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn seller_floor_only_checks_their_cheapest_sales() {
+        //the seller's cheapest MAX_FLOOR_SCAN sales only start in an hour and the next one is open now
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"100","starts_at":"{}"}}"#, 60 * MINUTE));
+        set_context(SELLER, SELLER, MAX_FLOOR_SCAN as u128 * STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        for token_id in 2..=MAX_FLOOR_SCAN {
+            set_context("game.near", SELLER, 0, 0);
+            contract.nft_on_approve(token_id.to_string(), SELLER.parse().unwrap(), 0, format!(r#"{{"sale_conditions":"100","starts_at":"{}"}}"#, 60 * MINUTE));
+        }
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("open".to_string(), SELLER.parse().unwrap(), 0, r#"{"sale_conditions":"200"}"#.to_string());
+        let floor = |contract: &Contract| contract.get_stats_by_seller_id(SELLER.parse().unwrap()).floor_price;
+        assert_eq!(floor(&contract), None);
+
+        //once the cheaper sales start they set the floor
+        set_context("bob.near", "bob.near", 0, 60 * MINUTE);
+        assert_eq!(floor(&contract), Some(U128(100)));
+    }
+
+    #[test]
+    fn payout_ignoring_fee_is_refunded() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        //the payout sums to the full price instead of the price minus the fee
+        assert!(!payout_accepted(&mut contract, 1000, 25, &format!(r#"{{"payout":{{"{}":"1000"}}}}"#, SELLER)));
+
+        assert_eq!(contract.get_fee_balance(None).0, 0);
+        assert_eq!(contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()).sales_count, 0);
+    }
+
+    #[test]
+    fn over_allocated_payouts_are_rejected() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        //the payout adds up to more than the price
+        assert!(!payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"900","{}":"200"}}}}"#, SELLER, NFT_CONTRACT)));
+
+        //the payout pays more accounts than the market can pay out at once
+        let payout: Vec<String> = (0..=MAX_LEN_PAYOUT).map(|i| format!(r#""royalty{}.near":"{}""#, i, if i == 0 { 1000 - MAX_LEN_PAYOUT } else { 1 })).collect();
+        assert!(!payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{{}}}}}"#, payout.join(","))));
+
+        assert!(payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"900","{}":"100"}}}}"#, SELLER, NFT_CONTRACT)));
+    }
+
+    #[test]
+    fn failed_transfer_refunds_buyer() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        //the payout was checked but the token couldn't be transferred
+        set_promise_results(vec![PromiseResult::Failed]);
+        let payout = HashMap::from([(SELLER.parse().unwrap(), U128(1000))]);
+        contract.resolve_purchase("alice.near".parse().unwrap(), CheckedPurchase { price: U128(1000), payout, context: purchase_context("1", 0) });
+
+        assert_eq!(contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()).sales_count, 0);
+        assert!(near_sdk::test_utils::get_logs()[0].contains(r#""reason":"transfer_failed""#));
+    }
+
+    #[test]
+    fn transferred_payout_must_match_checked_payout() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        //nft_transfer_payout routes the whole price to the nft contract after nft_payout was checked
+        set_promise_result(&format!(r#"{{"payout":{{"{}":"1000"}}}}"#, NFT_CONTRACT));
+        let payout = HashMap::from([(SELLER.parse().unwrap(), U128(1000))]);
+        contract.resolve_purchase("alice.near".parse().unwrap(), CheckedPurchase { price: U128(1000), payout, context: purchase_context("1", 0) });
+
+        //the checked payout is paid out instead
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[1].contains(&format!(r#""payout":{{"{}":"1000"}}"#, SELLER)));
+        assert_eq!(contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()).sales_count, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot withdraw more than the fee balance")]
+    fn cannot_withdraw_more_than_fee_balance() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        set_context(MARKET, MARKET, 1, 0);
+        contract.withdraw_fees(None, Some(U128(1)));
+    }
+
+    #[test]
+    fn failed_ft_fee_withdrawal_is_put_back() {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        let ft_token_id: AccountId = FT_CONTRACT.parse().unwrap();
+        contract.internal_record_sale(&NFT_CONTRACT.parse().unwrap(), Some(&ft_token_id), 1000, 25);
+
+        //the fees leave the treasury while the transfer is in flight
+        set_context(MARKET, MARKET, 1, 0);
+        contract.withdraw_fees(Some(ft_token_id.clone()), None);
+        assert_eq!(contract.get_fee_balance(Some(ft_token_id.clone())).0, 0);
+
+        //the owner isn't registered on the token contract so the transfer fails and the fees can be withdrawn again
+        set_promise_results(vec![PromiseResult::Failed]);
+        assert_eq!(contract.resolve_withdraw_fees(ft_token_id.clone(), U128(25)).0, 0);
+        assert_eq!(contract.get_fee_balance(Some(ft_token_id.clone())).0, 25);
+
+        set_promise_result("null");
+        assert_eq!(contract.resolve_withdraw_fees(ft_token_id.clone(), U128(25)).0, 25);
+        assert_eq!(contract.get_fee_balance(Some(ft_token_id)).0, 25);
+    }
+
+    #[test]
+    fn prune_removes_sale_for_transferred_token() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        set_promise_result(&format!(r#"{{"owner_id":"alice.near","approved_account_ids":{{"{}":0}}}}"#, MARKET));
+        assert!(contract.resolve_prune_sale(NFT_CONTRACT.parse().unwrap(), "1".to_string(), 0));
+
+        assert_eq!(contract.get_supply_sales().0, 0);
+        assert_eq!(contract.get_supply_by_owner_id(SELLER.parse().unwrap()).0, 0);
+    }
+
+    #[test]
+    fn prune_keeps_valid_sale() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        set_promise_result(&format!(r#"{{"owner_id":"{}","approved_account_ids":{{"{}":0}}}}"#, SELLER, MARKET));
+        assert!(!contract.resolve_prune_sale(NFT_CONTRACT.parse().unwrap(), "1".to_string(), 0));
+
+        assert_eq!(contract.get_supply_sales().0, 1);
+    }
+
+    #[test]
+    fn revoke_hook_removes_sale() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_revoke("1".to_string());
+
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    //lists a second token from the seller at the given price
+    fn list_token(contract: &mut Contract, token_id: &str, price: u128) {
+        set_context(SELLER, SELLER, STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve(token_id.to_string(), SELLER.parse().unwrap(), 0, format!(r#"{{"sale_conditions":"{}"}}"#, price));
+    }
+
+    fn sale_key(token_id: &str) -> SaleKey {
+        SaleKey { nft_contract_id: NFT_CONTRACT.parse().unwrap(), token_id: token_id.to_string() }
+    }
+
+    #[test]
+    fn offer_many_skips_sales_that_do_not_fit_deposit() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, NEAR));
+        list_token(&mut contract, "2", 3 * NEAR);
+
+        set_context("alice.near", "alice.near", 2 * NEAR, 0);
+        let purchased = contract.offer_many(vec![sale_key("1"), sale_key("2"), sale_key("missing")]);
+
+        assert_eq!(purchased, vec![format!("{}{}1", NFT_CONTRACT, DELIMITER)]);
+        assert_eq!(contract.get_supply_sales().0, 1);
+    }
+
+    #[test]
+    fn offer_many_fits_max_purchases_in_one_transaction() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, NEAR));
+        for token_id in 2..=MAX_OFFER_MANY {
+            list_token(&mut contract, &token_id.to_string(), NEAR);
+        }
+
+        //every purchase's GAS has to fit in the GAS of a single transaction
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(MARKET.parse().unwrap())
+            .predecessor_account_id("alice.near".parse().unwrap())
+            .attached_deposit(MAX_OFFER_MANY as u128 * NEAR)
+            .prepaid_gas(Gas(300_000_000_000_000))
+            .build());
+        let purchased = contract.offer_many((1..=MAX_OFFER_MANY).map(|token_id| sale_key(&token_id.to_string())).collect());
+
+        assert_eq!(purchased.len(), MAX_OFFER_MANY);
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    fn offer_many_budgets_purchases_as_near_purchases() {
+        //batched purchases are always NEAR sales so they don't reserve the FT resolve budget
+        assert_eq!(GAS_PER_OFFER_MANY_PURCHASE, 85_000_000_000_000);
+        assert_eq!(MAX_OFFER_MANY, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot purchase more than")]
+    fn offer_many_rejects_more_than_max_purchases() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, NEAR));
+
+        set_context("alice.near", "alice.near", NEAR, 0);
+        contract.offer_many((0..=MAX_OFFER_MANY).map(|token_id| sale_key(&token_id.to_string())).collect());
+    }
+
+    #[test]
+    fn update_prices_and_remove_sales_in_batch() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, NEAR));
+        list_token(&mut contract, "2", NEAR);
+
+        set_context(SELLER, SELLER, 1, 0);
+        contract.update_prices(vec![
+            PriceUpdate { nft_contract_id: NFT_CONTRACT.parse().unwrap(), token_id: "1".to_string(), price: U128(5) },
+            PriceUpdate { nft_contract_id: NFT_CONTRACT.parse().unwrap(), token_id: "2".to_string(), price: U128(6) },
+        ]);
+        assert_eq!(contract.get_sale(format!("{}{}2", NFT_CONTRACT, DELIMITER)).unwrap().sale_conditions.0, 6);
+
+        contract.remove_sales(vec![sale_key("1"), sale_key("2")]);
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must be sale owner")]
+    fn remove_sales_rejects_other_owners_sales() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}"}}"#, NEAR));
+
+        set_context("alice.near", "alice.near", 1, 0);
+        contract.remove_sales(vec![sale_key("1")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sale has not started")]
+    fn offer_rejected_before_sale_starts() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}","starts_at":"{}"}}"#, NEAR, MINUTE));
+
+        set_context("alice.near", "alice.near", NEAR, 0);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Sale has expired")]
+    fn offer_rejected_after_sale_expires() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}","expires_at":"{}"}}"#, NEAR, MINUTE));
+
+        set_context("alice.near", "alice.near", NEAR, MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    fn anyone_can_sweep_expired_sales() {
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"{}","expires_at":"{}"}}"#, NEAR, MINUTE));
+        list_token(&mut contract, "2", NEAR);
+
+        set_context("bob.near", "bob.near", 0, 2 * MINUTE);
+        assert_eq!(contract.remove_expired_sales(vec![sale_key("1"), sale_key("2")]), 1);
+
+        assert_eq!(contract.get_supply_sales().0, 1);
+        assert_eq!(contract.get_supply_by_owner_id(SELLER.parse().unwrap()).0, 1);
+    }
+
+    fn token_ids(sales: Vec<Sale>) -> Vec<String> {
+        sales.into_iter().map(|sale| sale.token_id).collect()
+    }
+
+    #[test]
+    fn sales_are_ordered_by_price() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"300"}"#);
+        list_token(&mut contract, "2", 100);
+        list_token(&mut contract, "3", 200);
+        list_token(&mut contract, "4", 200);
+        let nft_contract_id: AccountId = NFT_CONTRACT.parse().unwrap();
+
+        assert_eq!(contract.get_floor_price(nft_contract_id.clone()), Some(U128(100)));
+        assert_eq!(token_ids(contract.get_sales_by_price(nft_contract_id.clone(), None, None, None, None, Some(2))), vec!["2", "3"]);
+        assert_eq!(
+            token_ids(contract.get_sales_by_price(nft_contract_id.clone(), Some(U128(150)), Some(U128(250)), None, None, Some(10))),
+            vec!["3", "4"]
+        );
+        assert_eq!(
+            token_ids(contract.get_sales_by_price(nft_contract_id.clone(), None, Some(U128(250)), Some(true), None, Some(10))),
+            vec!["4", "3", "2"]
+        );
+    }
+
+    #[test]
+    fn price_views_skip_sales_outside_their_purchase_window() {
+        //the cheapest sale expires after a minute and the next one only starts after three minutes
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"100","expires_at":"{}"}}"#, MINUTE));
+        set_context(SELLER, SELLER, 2 * STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("2".to_string(), SELLER.parse().unwrap(), 0, format!(r#"{{"sale_conditions":"200","starts_at":"{}"}}"#, 3 * MINUTE));
+        contract.nft_on_approve("3".to_string(), SELLER.parse().unwrap(), 0, r#"{"sale_conditions":"300"}"#.to_string());
+        let nft_contract_id: AccountId = NFT_CONTRACT.parse().unwrap();
+
+        for (timestamp, floor, sales) in [(0, 100, vec!["1", "3"]), (2 * MINUTE, 300, vec!["3"]), (3 * MINUTE, 200, vec!["2", "3"])] {
+            set_context("bob.near", "bob.near", 0, timestamp);
+            assert_eq!(contract.get_floor_price(nft_contract_id.clone()), Some(U128(floor)));
+            assert_eq!(token_ids(contract.get_sales_by_price(nft_contract_id.clone(), None, None, None, None, Some(10))), sales);
+        }
+    }
+
+    #[test]
+    fn floor_price_only_checks_the_cheapest_sales() {
+        //the cheapest MAX_FLOOR_SCAN sales expire after a minute and the next one never does
+        let mut contract = setup_sale(&format!(r#"{{"sale_conditions":"100","expires_at":"{}"}}"#, MINUTE));
+        set_context(SELLER, SELLER, MAX_FLOOR_SCAN as u128 * STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+        for token_id in 2..=MAX_FLOOR_SCAN {
+            set_context(NFT_CONTRACT, SELLER, 0, 0);
+            contract.nft_on_approve(token_id.to_string(), SELLER.parse().unwrap(), 0, format!(r#"{{"sale_conditions":"100","expires_at":"{}"}}"#, MINUTE));
+        }
+        contract.nft_on_approve("open".to_string(), SELLER.parse().unwrap(), 0, r#"{"sale_conditions":"200"}"#.to_string());
+        let nft_contract_id: AccountId = NFT_CONTRACT.parse().unwrap();
+
+        set_context("bob.near", "bob.near", 0, 2 * MINUTE);
+        assert_eq!(contract.get_floor_price(nft_contract_id.clone()), None);
+
+        //clearing out the expired sales brings the open one within reach
+        for first_token_id in (1..=MAX_FLOOR_SCAN).step_by(10) {
+            set_context("bob.near", "bob.near", 0, 2 * MINUTE);
+            let expired = (first_token_id..first_token_id + 10).map(|token_id| sale_key(&token_id.to_string())).collect();
+            assert_eq!(contract.remove_expired_sales(expired), 10);
+        }
+        assert_eq!(contract.get_floor_price(nft_contract_id), Some(U128(200)));
+    }
+
+    #[test]
+    fn relisting_does_not_need_extra_storage() {
+        //the seller only paid for the one sale they already have
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("1".to_string(), SELLER.parse().unwrap(), 1, r#"{"sale_conditions":"2000"}"#.to_string());
+
+        let sale = contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).unwrap();
+        assert_eq!(sale.sale_conditions.0, 2000);
+        assert_eq!(sale.approval_id, 1);
+        assert_eq!(contract.get_supply_by_owner_id(SELLER.parse().unwrap()).0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient storage paid")]
+    fn listing_another_token_needs_storage() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("2".to_string(), SELLER.parse().unwrap(), 0, r#"{"sale_conditions":"2000"}"#.to_string());
+    }
+
+    #[test]
+    fn price_index_follows_updates_and_removals() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"300"}"#);
+        list_token(&mut contract, "2", 100);
+        let nft_contract_id: AccountId = NFT_CONTRACT.parse().unwrap();
+
+        set_context(SELLER, SELLER, 1, 0);
+        contract.update_price(nft_contract_id.clone(), "1".to_string(), U128(50));
+        assert_eq!(contract.get_floor_price(nft_contract_id.clone()), Some(U128(50)));
+
+        contract.remove_sale(nft_contract_id.clone(), "1".to_string());
+        contract.remove_sale(nft_contract_id.clone(), "2".to_string());
+        assert_eq!(contract.get_floor_price(nft_contract_id.clone()), None);
+        assert!(contract.get_sales_by_price(nft_contract_id, None, None, None, None, Some(10)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT contract is denied")]
+    fn denied_nft_contract_cannot_be_listed() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.add_denied_nft_contract_ids(vec![NFT_CONTRACT.parse().unwrap()]);
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("2".to_string(), SELLER.parse().unwrap(), 1, r#"{"sale_conditions":"1000"}"#.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT contract is not on the allowlist")]
+    fn allowlist_blocks_unknown_nft_contracts() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_allowlist_enabled(true);
+        contract.add_allowed_nft_contract_ids(vec!["real.near".parse().unwrap()]);
+        assert!(!contract.is_nft_contract_allowed(NFT_CONTRACT.parse().unwrap()));
+
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("2".to_string(), SELLER.parse().unwrap(), 1, r#"{"sale_conditions":"1000"}"#.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marketplace is paused")]
+    fn paused_marketplace_blocks_purchases() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_paused(true);
+
+        set_context("alice.near", "alice.near", 1000, 0);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    fn owner_can_delist_and_verify() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 1, 0);
+        contract.set_nft_contract_verified(NFT_CONTRACT.parse().unwrap(), true);
+        contract.owner_remove_sale(NFT_CONTRACT.parse().unwrap(), "1".to_string(), "impersonation".to_string());
+
+        assert!(contract.is_nft_contract_verified(NFT_CONTRACT.parse().unwrap()));
+        assert!(contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).is_none());
+    }
+
+    #[test]
+    fn storage_withdraw_is_limited_to_available_balance() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(SELLER, SELLER, STORAGE_PER_SALE * 2, 0);
+        contract.storage_deposit(None, None);
+
+        let balance = contract.storage_balance_of(SELLER.parse().unwrap()).unwrap();
+        assert_eq!(balance.total.0, STORAGE_PER_SALE * 3);
+        assert_eq!(balance.available.0, STORAGE_PER_SALE * 2);
+
+        set_context(SELLER, SELLER, 1, 0);
+        let balance = contract.storage_withdraw(Some(U128(STORAGE_PER_SALE)));
+        assert_eq!(balance.total.0, STORAGE_PER_SALE * 2);
+        assert_eq!(balance.available.0, STORAGE_PER_SALE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot withdraw more than the available storage balance")]
+    fn storage_in_use_cannot_be_withdrawn() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(SELLER, SELLER, 1, 0);
+        contract.storage_withdraw(Some(U128(1)));
+    }
+
+    #[test]
+    fn storage_registration_only_keeps_minimum() {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        set_context(SELLER, SELLER, STORAGE_PER_SALE * 2, 0);
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total.0, STORAGE_PER_SALE);
+
+        //registering again doesn't add to the balance
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total.0, STORAGE_PER_SALE);
+        assert!(contract.storage_balance_of("alice.near".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn storage_unregister_with_force_removes_sales() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(SELLER, SELLER, 1, 0);
+        assert!(contract.storage_unregister(Some(true)));
+
+        assert!(contract.storage_balance_of(SELLER.parse().unwrap()).is_none());
+        assert!(contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).is_none());
+        assert!(!contract.storage_unregister(None));
+    }
+
+    #[test]
+    fn payout_breaking_policy_is_refunded() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_payout_policy(2_000, 5_000);
+
+        //the nft contract routes 30% of the price to itself
+        assert!(!payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"700","{}":"300"}}}}"#, SELLER, NFT_CONTRACT)));
+        assert!(near_sdk::test_utils::get_logs()[0].contains(r#""reason":"royalty_above_maximum""#));
+
+        assert!(payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"900","{}":"100"}}}}"#, SELLER, NFT_CONTRACT)));
+    }
+
+    #[test]
+    fn verified_payout_is_checked_against_royalty_schedule() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_nft_contract_verified(NFT_CONTRACT.parse().unwrap(), true);
+        contract.set_royalty_schedule(NFT_CONTRACT.parse().unwrap(), HashMap::from([("artist.near".parse().unwrap(), 500)]));
+
+        //an account outside of the schedule can't receive a royalty
+        assert!(!payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"950","{}":"50"}}}}"#, SELLER, NFT_CONTRACT)));
+
+        //the artist can't receive more than their share
+        assert!(!payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"900","artist.near":"100"}}}}"#, SELLER)));
+
+        assert!(payout_accepted(&mut contract, 1000, 0, &format!(r#"{{"payout":{{"{}":"950","artist.near":"50"}}}}"#, SELLER)));
+    }
+
+    #[test]
+    fn private_sale_is_listed_for_reserved_buyers() {
+        let contract = setup_sale(r#"{"sale_conditions":"1000","reserved_for":["alice.near","bob.near"]}"#);
+
+        assert_eq!(contract.get_supply_by_reserved_for("alice.near".parse().unwrap()).0, 1);
+        assert_eq!(contract.get_sales_by_reserved_for("bob.near".parse().unwrap(), None, Some(10)).len(), 1);
+        //private sales aren't part of the public price index
+        assert_eq!(contract.get_floor_price(NFT_CONTRACT.parse().unwrap()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sale is reserved for other accounts")]
+    fn private_sale_cannot_be_purchased_by_others() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000","reserved_for":["alice.near"]}"#);
+
+        set_context("carol.near", "carol.near", 1000, 0);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    fn removing_private_sale_clears_reserved_index() {
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000","reserved_for":["alice.near"]}"#);
+
+        set_context(SELLER, SELLER, 1, 0);
+        contract.remove_sale(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        assert_eq!(contract.get_supply_by_reserved_for("alice.near".parse().unwrap()).0, 0);
+    }
+
+    #[test]
+    fn dutch_auction_price_decays_linearly() {
+        let contract = setup_sale(&format!(
+            r#"{{"sale_conditions":"1000","dutch_auction":{{"starts_at":"0","ends_at":"{}","floor_price":"200"}}}}"#,
+            100 * MINUTE
+        ));
+        set_context("alice.near", "alice.near", 0, 50 * MINUTE);
+        assert_eq!(contract.get_current_price(NFT_CONTRACT.parse().unwrap(), "1".to_string()).0, 600);
+        set_context("alice.near", "alice.near", 0, 200 * MINUTE);
+        assert_eq!(contract.get_current_price(NFT_CONTRACT.parse().unwrap(), "1".to_string()).0, 200);
+    }
+
+    #[test]
+    fn dutch_auction_price_decays_at_near_scale() {
+        //a 10 NEAR auction over a day multiplies a yoctoNEAR price by nanoseconds, which doesn't fit in a u128
+        let contract = setup_sale(&format!(
+            r#"{{"sale_conditions":"10000000000000000000000000","dutch_auction":{{"starts_at":"0","ends_at":"{}","floor_price":"0"}}}}"#,
+            24 * 60 * MINUTE
+        ));
+        set_context("alice.near", "alice.near", 0, 18 * 60 * MINUTE);
+        assert_eq!(contract.get_current_price(NFT_CONTRACT.parse().unwrap(), "1".to_string()).0, 10 * NEAR / 4);
+    }
+
+    #[test]
+    fn dutch_auction_price_decays_in_steps() {
+        let contract = setup_sale(&format!(
+            r#"{{"sale_conditions":"1000","dutch_auction":{{"starts_at":"0","ends_at":"{}","floor_price":"0","step_interval":"{}"}}}}"#,
+            100 * MINUTE, 30 * MINUTE
+        ));
+        set_context("alice.near", "alice.near", 0, 50 * MINUTE);
+        assert_eq!(contract.get_current_price(NFT_CONTRACT.parse().unwrap(), "1".to_string()).0, 700);
+    }
+
+    #[test]
+    fn dutch_auction_is_purchased_at_current_price() {
+        let mut contract = setup_sale(&format!(
+            r#"{{"sale_conditions":"1000","dutch_auction":{{"starts_at":"0","ends_at":"{}","floor_price":"0"}}}}"#,
+            100 * MINUTE
+        ));
+
+        //the buyer overpays and the difference is refunded
+        set_context("alice.near", "alice.near", 1000, 75 * MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        assert!(contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached deposit must be greater than or equal to the current price")]
+    fn dutch_auction_requires_current_price() {
+        let mut contract = setup_sale(&format!(
+            r#"{{"sale_conditions":"1000","dutch_auction":{{"starts_at":"0","ends_at":"{}","floor_price":"0"}}}}"#,
+            100 * MINUTE
+        ));
+
+        set_context("alice.near", "alice.near", 200, 75 * MINUTE);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    const SWAP_LISTING: &str = r#"{"swap_for":{"nft_contract_id":"game.near"}}"#;
+
+    #[test]
+    #[should_panic(expected = "Sale is a swap listing")]
+    fn swap_listing_cannot_be_purchased() {
+        let mut contract = setup_sale(SWAP_LISTING);
+
+        set_context("alice.near", "alice.near", NEAR, 0);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is not accepted by the swap listing")]
+    fn swap_requires_requested_collection() {
+        let mut contract = setup_sale(SWAP_LISTING);
+
+        set_context("other.near", "alice.near", 0, 0);
+        contract.nft_on_approve(
+            "7".to_string(),
+            "alice.near".parse().unwrap(),
+            0,
+            format!(r#"{{"accept_swap":{{"nft_contract_id":"{}","token_id":"1"}}}}"#, NFT_CONTRACT),
+        );
+    }
+
+    #[test]
+    fn accepted_swap_takes_down_listing_once_escrowed() {
+        let mut contract = setup_sale(SWAP_LISTING);
+
+        set_context("game.near", "alice.near", 0, 0);
+        contract.nft_on_approve(
+            "7".to_string(),
+            "alice.near".parse().unwrap(),
+            0,
+            format!(r#"{{"accept_swap":{{"nft_contract_id":"{}","token_id":"1"}}}}"#, NFT_CONTRACT),
+        );
+        //the listing stays up until the counterparty's token is in escrow
+        assert!(contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).is_some());
+
+        set_promise_result("null");
+        contract.resolve_swap_escrow(
+            SaleKey { nft_contract_id: NFT_CONTRACT.parse().unwrap(), token_id: "1".to_string() },
+            0,
+            "alice.near".parse().unwrap(),
+            "game.near".parse().unwrap(),
+            "7".to_string(),
+        );
+        assert!(contract.get_sale(format!("{}{}1", NFT_CONTRACT, DELIMITER)).is_none());
+    }
+
+    #[test]
+    fn failed_escrow_release_can_be_claimed() {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        let escrowed_token = format!("game.near{}7", DELIMITER);
+
+        //sending the escrowed token to the seller fails so it's kept for them
+        set_promise_results(vec![PromiseResult::Failed]);
+        assert!(!contract.resolve_escrow_release("game.near".parse().unwrap(), "7".to_string(), SELLER.parse().unwrap()));
+        assert_eq!(contract.get_escrowed_token_owner(escrowed_token.clone()), Some(SELLER.parse().unwrap()));
+        assert!(near_sdk::test_utils::get_logs()[0].contains(r#""event":"escrow_claimable""#));
+
+        //the claim retries the transfer and a successful transfer leaves nothing behind
+        set_context(SELLER, SELLER, 1, 0);
+        contract.claim_escrowed_token("game.near".parse().unwrap(), "7".to_string());
+        assert!(contract.get_escrowed_token_owner(escrowed_token.clone()).is_none());
+        set_promise_result("null");
+        assert!(contract.resolve_escrow_release("game.near".parse().unwrap(), "7".to_string(), SELLER.parse().unwrap()));
+        assert!(contract.get_escrowed_token_owner(escrowed_token).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner of the escrowed token can claim it")]
+    fn escrowed_token_can_only_be_claimed_by_its_owner() {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        set_promise_results(vec![PromiseResult::Failed]);
+        contract.resolve_escrow_release("game.near".parse().unwrap(), "7".to_string(), SELLER.parse().unwrap());
+
+        set_context("alice.near", "alice.near", 1, 0);
+        contract.claim_escrowed_token("game.near".parse().unwrap(), "7".to_string());
+    }
+
+    fn setup_bundle() -> Contract {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        set_context(SELLER, SELLER, STORAGE_PER_SALE * 3, 0);
+        contract.storage_deposit(None, None);
+        let bundle_id = contract.create_bundle(U128(1000));
+
+        //add a token from two different nft contracts with a 3:1 split
+        set_context(NFT_CONTRACT, SELLER, 0, 0);
+        contract.nft_on_approve("1".to_string(), SELLER.parse().unwrap(), 0, format!(r#"{{"bundle_id":"{}","weight":3}}"#, bundle_id.0));
+        set_context("game.near", SELLER, 0, 0);
+        contract.nft_on_approve("7".to_string(), SELLER.parse().unwrap(), 0, format!(r#"{{"bundle_id":"{}","weight":1}}"#, bundle_id.0));
+        contract
+    }
+
+    #[test]
+    fn bundle_price_is_split_by_weight() {
+        let contract = setup_bundle();
+
+        let bundle = contract.get_bundle_for_token("game.near".parse().unwrap(), "7".to_string()).unwrap();
+        assert_eq!(bundle.items.len(), 2);
+        assert_eq!(bundle.split(1000), vec![750, 250]);
+        assert_eq!(contract.get_bundles_by_owner_id(SELLER.parse().unwrap(), None, Some(10)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient storage paid")]
+    fn bundle_tokens_need_storage() {
+        let mut contract = setup_bundle();
+
+        set_context("other.near", SELLER, 0, 0);
+        contract.nft_on_approve("9".to_string(), SELLER.parse().unwrap(), 0, r#"{"bundle_id":"0","weight":1}"#.to_string());
+    }
+
+    #[test]
+    fn bundle_purchase_refunds_failed_tokens() {
+        let mut contract = setup_bundle();
+
+        set_context("alice.near", "alice.near", 1000, 0);
+        contract.offer_bundle(U64(0));
+        assert!(contract.get_bundle(U64(0)).is_none());
+
+        //the first token is transferred and paid out, the second transfer fails
+        let game_context = PurchaseContext { nft_contract_id: "game.near".parse().unwrap(), token_id: "7".to_string(), ..purchase_context("7", 0) };
+        let purchases = vec![
+            CheckedPurchase { price: U128(750), payout: HashMap::from([(SELLER.parse().unwrap(), U128(750))]), context: purchase_context("1", 0) },
+            CheckedPurchase { price: U128(250), payout: HashMap::from([(SELLER.parse().unwrap(), U128(250))]), context: game_context },
+        ];
+        set_promise_results(vec![PromiseResult::Successful(format!(r#"{{"payout":{{"{}":"750"}}}}"#, SELLER).into_bytes()), PromiseResult::Failed]);
+        contract.resolve_bundle_purchase("alice.near".parse().unwrap(), purchases);
+
+        assert_eq!(contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()).sales_count, 1);
+        assert_eq!(contract.get_volume_by_nft_contract_id("game.near".parse().unwrap()).sales_count, 0);
+    }
+
+    #[test]
+    fn bundle_payouts_are_checked_before_transfer() {
+        let mut contract = setup_bundle();
+        let bundle = contract.get_bundle(U64(0)).unwrap();
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_payout_policy(10_000, 5_000);
+
+        //the second token's nft contract routes its whole share to itself so only the first token is transferred
+        set_promise_results(vec![
+            PromiseResult::Successful(format!(r#"{{"payout":{{"{}":"750"}}}}"#, SELLER).into_bytes()),
+            PromiseResult::Successful(r#"{"payout":{"game.near":"250"}}"#.as_bytes().to_vec()),
+        ]);
+        let purchases = contract.internal_bundle_purchases(&bundle);
+        let result = contract.resolve_bundle_payouts("alice.near".parse().unwrap(), purchases);
+
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains(r#""token_id":"7","price":"250""#));
+        assert!(logs[0].contains(r#""reason":"seller_share_below_minimum""#));
+    }
 
-    // return how much storage an account has paid for
-    pub fn storage_balance_of(&self, account_id: AccountId) -> U128 {
-        U128(self.storage_deposits.get(&account_id).unwrap_or(0))
+    #[test]
+    fn bundle_payouts_are_checked_against_the_fee_at_purchase() {
+        let mut contract = setup_bundle();
+        let bundle = contract.get_bundle(U64(0)).unwrap();
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_fee_basis_points(1_000);
+        let purchases = contract.internal_bundle_purchases(&bundle);
+
+        //the fee is lowered while the payouts are being queried. They still only have to add up to the price minus the old fee
+        contract.set_fee_basis_points(0);
+        set_promise_results(vec![
+            PromiseResult::Successful(format!(r#"{{"payout":{{"{}":"675"}}}}"#, SELLER).into_bytes()),
+            PromiseResult::Successful(format!(r#"{{"payout":{{"{}":"225"}}}}"#, SELLER).into_bytes()),
+        ]);
+        let result = contract.resolve_bundle_payouts("alice.near".parse().unwrap(), purchases);
+
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        assert!(near_sdk::test_utils::get_logs().is_empty());
+    }
+
+    fn setup_lazy_mint() -> Contract {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        set_context(SELLER, SELLER, STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+
+        //the series contract confirms the seller owns series 1 which has a price of 100
+        set_promise_result(&format!(r#"{{"series_id":1,"owner_id":"{}","price":"100"}}"#, SELLER));
+        let listing = LazyMintListing {
+            owner_id: SELLER.parse().unwrap(),
+            nft_contract_id: NFT_CONTRACT.parse().unwrap(),
+            series_id: U64(1),
+            price: U128(1000),
+            remaining: Some(2),
+        };
+        assert!(contract.resolve_list_lazy_mint(listing));
+        contract
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lazy_mint_failure_restores_edition() {
+        let mut contract = setup_lazy_mint();
+        assert_eq!(contract.get_lazy_mints_by_owner_id(SELLER.parse().unwrap(), None, Some(10)).len(), 1);
+
+        set_context("alice.near", "alice.near", 1500, 0);
+        contract.offer_lazy_mint(NFT_CONTRACT.parse().unwrap(), U64(1));
+        assert_eq!(contract.get_lazy_mint(NFT_CONTRACT.parse().unwrap(), U64(1)).unwrap().remaining, Some(1));
+
+        //the mint fails so the buyer is refunded and the edition can be sold again
+        testing_env!(
+            VMContextBuilder::new().current_account_id(MARKET.parse().unwrap()).predecessor_account_id(MARKET.parse().unwrap()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.resolve_lazy_mint(
+            NFT_CONTRACT.parse().unwrap(), U64(1), SELLER.parse().unwrap(), "alice.near".parse().unwrap(), U128(1000), U128(0)
+        ));
+        assert_eq!(contract.get_lazy_mint(NFT_CONTRACT.parse().unwrap(), U64(1)).unwrap().remaining, Some(2));
+        assert_eq!(contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()).sales_count, 0);
+    }
+
+    #[test]
+    fn lazy_mint_listing_removed_when_sold_out() {
+        let mut contract = setup_lazy_mint();
+
+        for _ in 0..2 {
+            set_context("alice.near", "alice.near", 1000, 0);
+            contract.offer_lazy_mint(NFT_CONTRACT.parse().unwrap(), U64(1));
+        }
+        assert!(contract.get_lazy_mint(NFT_CONTRACT.parse().unwrap(), U64(1)).is_none());
+        assert!(contract.get_lazy_mints_by_owner_id(SELLER.parse().unwrap(), None, Some(10)).is_empty());
+
+        set_promise_result("");
+        contract.resolve_lazy_mint(
+            NFT_CONTRACT.parse().unwrap(), U64(1), SELLER.parse().unwrap(), "alice.near".parse().unwrap(), U128(1000), U128(0)
+        );
+        assert_eq!(contract.get_volume_by_nft_contract_id(NFT_CONTRACT.parse().unwrap()).sales_count, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Series must have a price")]
+    fn lazy_mint_requires_priced_series() {
+        let mut contract = Contract::new(MARKET.parse().unwrap());
+        set_context(SELLER, SELLER, STORAGE_PER_SALE, 0);
+        contract.storage_deposit(None, None);
+
+        set_promise_result(&format!(r#"{{"series_id":1,"owner_id":"{}","price":null}}"#, SELLER));
+        contract.resolve_list_lazy_mint(LazyMintListing {
+            owner_id: SELLER.parse().unwrap(),
+            nft_contract_id: NFT_CONTRACT.parse().unwrap(),
+            series_id: U64(1),
+            price: U128(1000),
+            remaining: None,
+        });
+    }
+
+    #[test]
+    fn migrate_keeps_live_sales() {
+        set_context(MARKET, MARKET, 0, 0);
+
+        //write the state of the first version of the marketplace with a live sale and no version key
+        let mut old_state = ContractV1 {
+            owner_id: MARKET.parse().unwrap(),
+            sales: UnorderedMap::new(StorageKey::Sales),
+            by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
+            by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+        };
+        let contract_and_token_id = format!("{}{}1", NFT_CONTRACT, DELIMITER);
+        old_state.sales.insert(&contract_and_token_id, &SaleV1 {
+            owner_id: SELLER.parse().unwrap(),
+            approval_id: 0,
+            nft_contract_id: NFT_CONTRACT.to_string(),
+            token_id: "1".to_string(),
+            sale_conditions: U128(1000),
+        });
+        let mut by_owner_id = UnorderedSet::new(StorageKey::ByOwnerIdInner { account_id_hash: hash_account_id(&SELLER.parse().unwrap()) }.try_to_vec().unwrap());
+        by_owner_id.insert(&contract_and_token_id);
+        old_state.by_owner_id.insert(&SELLER.parse().unwrap(), &by_owner_id);
+        old_state.storage_deposits.insert(&SELLER.parse().unwrap(), &STORAGE_PER_SALE);
+        let mut by_nft_contract_id = UnorderedSet::new(StorageKey::ByNFTContractIdInner { account_id_hash: hash_account_id(&NFT_CONTRACT.parse().unwrap()) }.try_to_vec().unwrap());
+        by_nft_contract_id.insert(&"1".to_string());
+        old_state.by_nft_contract_id.insert(&NFT_CONTRACT.parse().unwrap(), &by_nft_contract_id);
+        env::state_write(&old_state);
+
+        let contract = Contract::migrate();
+        assert_eq!(contract.get_state_version(), 2);
+
+        //the migrated state is stored in the frozen second layout, so reading it back goes through ContractV2
+        env::state_write(&contract);
+        let stored: ContractV2 = env::state_read().unwrap();
+        assert_eq!(stored.sales.len(), 1);
+        assert_eq!(stored.by_price.get(&NFT_CONTRACT.parse().unwrap()).unwrap().len(), 1);
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.get_supply_sales().0, 1);
+        assert_eq!(contract.get_sales_by_owner_id(SELLER.parse().unwrap(), None, Some(10)).len(), 1);
+        assert_eq!(contract.get_floor_price(NFT_CONTRACT.parse().unwrap()), Some(U128(1000)));
+        assert_eq!(contract.storage_balance_of(SELLER.parse().unwrap()).unwrap().total.0, STORAGE_PER_SALE);
+
+        //the migrated sale can still be purchased and taken down
+        set_context("alice.near", "alice.near", 1000, 0);
+        contract.offer(NFT_CONTRACT.parse().unwrap(), "1".to_string());
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    fn migrate_reads_versioned_state_as_v2() {
+        //a market created by this code is already marked with the second version
+        let mut contract = setup_sale(r#"{"sale_conditions":"1000"}"#);
+        set_context(MARKET, MARKET, 0, 0);
+        contract.set_fee_basis_points(500);
+        env::state_write(&contract);
+
+        let contract = Contract::migrate();
+        assert_eq!(contract.get_supply_sales().0, 1);
+        assert_eq!(contract.get_floor_price(NFT_CONTRACT.parse().unwrap()), Some(U128(1000)));
+        assert_eq!(contract.get_stats_by_seller_id(SELLER.parse().unwrap()).floor_price, Some(U128(1000)));
+        assert_eq!(contract.get_fee_basis_points(), 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn update_contract_is_owner_only() {
+        let contract = Contract::new(MARKET.parse().unwrap());
+        set_context(SELLER, SELLER, 0, 0);
+        contract.update_contract();
+    }
+}
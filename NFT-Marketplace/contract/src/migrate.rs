@@ -0,0 +1,193 @@
+use crate::*;
+
+//the state version is stored under its own key so the state can be read in the right layout before it's migrated
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+//the version of the state layout this code uses. This has to be increased every time the layout of the Contract changes
+const STATE_VERSION: u8 = 2;
+
+//struct that holds a sale as it was stored by the first version of the marketplace (fixed price NEAR sales only)
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SaleV1 {
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub sale_conditions: SalePriceInYoctoNear,
+}
+
+//the state of the first version of the marketplace. Contracts deployed before the state was versioned don't have a version key
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub owner_id: AccountId,
+    pub sales: UnorderedMap<ContractAndTokenId, SaleV1>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+}
+
+/*
+    the state of the second version of the marketplace (the first one with a version key). The collections only store
+    their prefix and length so their value types don't change how this struct is read. If a stored value changes its
+    layout, it needs its own snapshot like SaleV1.
+*/
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV2 {
+    pub owner_id: AccountId,
+    pub sales: UnorderedMap<ContractAndTokenId, Sale>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    pub approved_ft_token_ids: UnorderedSet<FungibleTokenId>,
+    pub buy_offers: UnorderedMap<BuyOfferId, BuyOffer>,
+    pub buy_offers_by_buyer_id: LookupMap<AccountId, UnorderedSet<BuyOfferId>>,
+    pub buy_offers_by_nft_contract_id: LookupMap<AccountId, UnorderedSet<BuyOfferId>>,
+    pub next_buy_offer_id: BuyOfferId,
+    pub fee_basis_points: u32,
+    pub near_fee_balance: Balance,
+    pub ft_fee_balances: LookupMap<FungibleTokenId, Balance>,
+    pub volume_by_nft_contract_id: LookupMap<AccountId, CollectionVolume>,
+    pub by_price: LookupMap<AccountId, TreeMap<(Balance, TokenId), ()>>,
+    pub sale_history: LookupMap<AccountId, VecDeque<SaleRecord>>,
+    pub paused: bool,
+    pub allowlist_enabled: bool,
+    pub allowed_nft_contract_ids: UnorderedSet<AccountId>,
+    pub denied_nft_contract_ids: UnorderedSet<AccountId>,
+    pub verified_nft_contract_ids: UnorderedSet<AccountId>,
+    pub max_royalty_basis_points: u32,
+    pub min_seller_basis_points: u32,
+    pub royalty_schedules: LookupMap<AccountId, HashMap<AccountId, u32>>,
+    pub by_reserved_for: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub bundles: UnorderedMap<BundleId, Bundle>,
+    pub bundles_by_owner_id: LookupMap<AccountId, UnorderedSet<BundleId>>,
+    pub bundle_by_token: LookupMap<ContractAndTokenId, BundleId>,
+    pub next_bundle_id: BundleId,
+    pub lazy_mints: UnorderedMap<String, LazyMintListing>,
+    pub lazy_mints_by_owner_id: LookupMap<AccountId, UnorderedSet<String>>,
+    pub stats_by_nft_contract_id: LookupMap<AccountId, MarketStats>,
+    pub stats_by_seller_id: LookupMap<AccountId, MarketStats>,
+    pub escrowed_tokens: UnorderedMap<ContractAndTokenId, AccountId>,
+    pub by_price_by_owner_id: LookupMap<AccountId, TreeMap<(Balance, ContractAndTokenId), ()>>,
+}
+
+//every layout the marketplace state has been stored in. New versions are added at the end
+#[allow(clippy::large_enum_variant)]
+pub enum VersionedContract {
+    V1(ContractV1),
+    V2(ContractV2),
+}
+
+impl VersionedContract {
+    //reads the state in the layout of the stored state version
+    fn read() -> Self {
+        let version = env::storage_read(STATE_VERSION_KEY).map_or(1, |version| version[0]);
+        match version {
+            1 => VersionedContract::V1(env::state_read().expect("No state to migrate")),
+            2 => VersionedContract::V2(env::state_read().expect("No state to migrate")),
+            _ => env::panic_str(&format!("Unknown state version {}", version)),
+        }
+    }
+}
+
+impl From<VersionedContract> for Contract {
+    fn from(state: VersionedContract) -> Self {
+        match state {
+            VersionedContract::V2(old_state) => Contract {
+                owner_id: old_state.owner_id,
+                sales: old_state.sales,
+                by_owner_id: old_state.by_owner_id,
+                by_nft_contract_id: old_state.by_nft_contract_id,
+                storage_deposits: old_state.storage_deposits,
+                approved_ft_token_ids: old_state.approved_ft_token_ids,
+                buy_offers: old_state.buy_offers,
+                buy_offers_by_buyer_id: old_state.buy_offers_by_buyer_id,
+                buy_offers_by_nft_contract_id: old_state.buy_offers_by_nft_contract_id,
+                next_buy_offer_id: old_state.next_buy_offer_id,
+                fee_basis_points: old_state.fee_basis_points,
+                near_fee_balance: old_state.near_fee_balance,
+                ft_fee_balances: old_state.ft_fee_balances,
+                volume_by_nft_contract_id: old_state.volume_by_nft_contract_id,
+                by_price: old_state.by_price,
+                sale_history: old_state.sale_history,
+                paused: old_state.paused,
+                allowlist_enabled: old_state.allowlist_enabled,
+                allowed_nft_contract_ids: old_state.allowed_nft_contract_ids,
+                denied_nft_contract_ids: old_state.denied_nft_contract_ids,
+                verified_nft_contract_ids: old_state.verified_nft_contract_ids,
+                max_royalty_basis_points: old_state.max_royalty_basis_points,
+                min_seller_basis_points: old_state.min_seller_basis_points,
+                royalty_schedules: old_state.royalty_schedules,
+                by_reserved_for: old_state.by_reserved_for,
+                bundles: old_state.bundles,
+                bundles_by_owner_id: old_state.bundles_by_owner_id,
+                bundle_by_token: old_state.bundle_by_token,
+                next_bundle_id: old_state.next_bundle_id,
+                lazy_mints: old_state.lazy_mints,
+                lazy_mints_by_owner_id: old_state.lazy_mints_by_owner_id,
+                stats_by_nft_contract_id: old_state.stats_by_nft_contract_id,
+                stats_by_seller_id: old_state.stats_by_seller_id,
+                escrowed_tokens: old_state.escrowed_tokens,
+                by_price_by_owner_id: old_state.by_price_by_owner_id,
+            },
+            VersionedContract::V1(mut old_state) => {
+                let mut contract = Contract::new(old_state.owner_id);
+                contract.by_owner_id = old_state.by_owner_id;
+                contract.by_nft_contract_id = old_state.by_nft_contract_id;
+                contract.storage_deposits = old_state.storage_deposits;
+
+                /*
+                    the sales are read in the old layout and written back under the same prefix in the new layout. The sale
+                    sets only hold the unique sale IDs so they stay valid. Every sale is converted in this call so very large
+                    markets need to be migrated before they grow beyond what fits in the gas limit.
+                */
+                let old_sales = old_state.sales.to_vec();
+                old_state.sales.clear();
+                for (contract_and_token_id, old_sale) in old_sales {
+                    let sale = Sale {
+                        owner_id: old_sale.owner_id,
+                        approval_id: old_sale.approval_id,
+                        nft_contract_id: old_sale.nft_contract_id,
+                        token_id: old_sale.token_id,
+                        sale_conditions: old_sale.sale_conditions,
+                        auction: None,
+                        ft_token_id: None,
+                        starts_at: None,
+                        expires_at: None,
+                        reserved_for: None,
+                        dutch_auction: None,
+                        swap_for: None,
+                    };
+                    contract.sales.insert(&contract_and_token_id, &sale);
+                    //the first version had no price index so every migrated sale is added to it
+                    contract.internal_index_price(&sale);
+                }
+                contract
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //migrates the state from any previous layout to the current one. This is called by update_contract right after the new code is deployed
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let contract: Contract = VersionedContract::read().into();
+        //store the version the state is now in
+        env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+        contract
+    }
+
+    // views
+    //returns the version of the state layout the marketplace uses
+    pub fn get_state_version(&self) -> u8 {
+        STATE_VERSION
+    }
+}
+
+impl Contract {
+    //internal method for marking a freshly initialized state with the current state version
+    pub(crate) fn internal_write_state_version() {
+        env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+    }
+}
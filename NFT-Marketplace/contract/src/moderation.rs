@@ -0,0 +1,141 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //Allows the owner to pause the marketplace. While paused, no new sales or buy offers can be created and nothing can be purchased
+    pub fn set_paused(&mut self, paused: bool) {
+        //make sure only the owner can pause the marketplace
+        self.assert_owner();
+        self.paused = paused;
+    }
+
+    //Allows the owner to only accept sales and buy offers from nft contracts on the allowlist
+    pub fn set_allowlist_enabled(&mut self, enabled: bool) {
+        //make sure only the owner can change how nft contracts are moderated
+        self.assert_owner();
+        self.allowlist_enabled = enabled;
+    }
+
+    //Allows the owner to add nft contracts to the allowlist. This is only enforced if the allowlist is enabled
+    pub fn add_allowed_nft_contract_ids(&mut self, nft_contract_ids: Vec<AccountId>) {
+        //make sure only the owner can change the allowlist
+        self.assert_owner();
+        for nft_contract_id in nft_contract_ids {
+            self.allowed_nft_contract_ids.insert(&nft_contract_id);
+        }
+    }
+
+    //Allows the owner to remove nft contracts from the allowlist. Existing sales are kept but can be delisted by the owner
+    pub fn remove_allowed_nft_contract_ids(&mut self, nft_contract_ids: Vec<AccountId>) {
+        //make sure only the owner can change the allowlist
+        self.assert_owner();
+        for nft_contract_id in nft_contract_ids {
+            self.allowed_nft_contract_ids.remove(&nft_contract_id);
+        }
+    }
+
+    //Allows the owner to deny nft contracts. Denied contracts can't be listed or receive buy offers even if they're on the allowlist
+    pub fn add_denied_nft_contract_ids(&mut self, nft_contract_ids: Vec<AccountId>) {
+        //make sure only the owner can change the denylist
+        self.assert_owner();
+        for nft_contract_id in nft_contract_ids {
+            self.denied_nft_contract_ids.insert(&nft_contract_id);
+        }
+    }
+
+    //Allows the owner to remove nft contracts from the denylist
+    pub fn remove_denied_nft_contract_ids(&mut self, nft_contract_ids: Vec<AccountId>) {
+        //make sure only the owner can change the denylist
+        self.assert_owner();
+        for nft_contract_id in nft_contract_ids {
+            self.denied_nft_contract_ids.remove(&nft_contract_id);
+        }
+    }
+
+    //Allows the owner to flag an nft contract as verified (the real collection) so frontends can tell it apart from impersonators
+    pub fn set_nft_contract_verified(&mut self, nft_contract_id: AccountId, verified: bool) {
+        //make sure only the owner can verify collections
+        self.assert_owner();
+        if verified {
+            self.verified_nft_contract_ids.insert(&nft_contract_id);
+        } else {
+            self.verified_nft_contract_ids.remove(&nft_contract_id);
+        }
+    }
+
+    //Allows the owner to take down any sale. The reason is logged in the delist event and any bidder is refunded
+    #[payable]
+    pub fn owner_remove_sale(&mut self, nft_contract_id: AccountId, token_id: TokenId, reason: String) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //make sure only the owner can take down other accounts' sales
+        self.assert_owner();
+        self.internal_cancel_sale(nft_contract_id, token_id, &reason);
+    }
+
+    // views
+    //return whether the marketplace is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    //return whether only nft contracts on the allowlist can be listed
+    pub fn is_allowlist_enabled(&self) -> bool {
+        self.allowlist_enabled
+    }
+
+    //return whether sales and buy offers can currently be created for an nft contract
+    pub fn is_nft_contract_allowed(&self, nft_contract_id: AccountId) -> bool {
+        !self.denied_nft_contract_ids.contains(&nft_contract_id)
+            && (!self.allowlist_enabled || self.allowed_nft_contract_ids.contains(&nft_contract_id))
+    }
+
+    //return whether an nft contract was verified by the owner
+    pub fn is_nft_contract_verified(&self, nft_contract_id: AccountId) -> bool {
+        self.verified_nft_contract_ids.contains(&nft_contract_id)
+    }
+
+    //return the nft contracts on the allowlist
+    pub fn get_allowed_nft_contract_ids(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        paginate(&self.allowed_nft_contract_ids, from_index, limit)
+    }
+
+    //return the nft contracts on the denylist
+    pub fn get_denied_nft_contract_ids(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        paginate(&self.denied_nft_contract_ids, from_index, limit)
+    }
+
+    //return the nft contracts that were verified by the owner
+    pub fn get_verified_nft_contract_ids(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        paginate(&self.verified_nft_contract_ids, from_index, limit)
+    }
+}
+
+//returns a page of the nft contracts in one of the moderation sets
+fn paginate(set: &UnorderedSet<AccountId>, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+    //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+    let start = u128::from(from_index.unwrap_or(U128(0)));
+
+    set.iter()
+        //skip to the index we specified in the start variable
+        .skip(start as usize)
+        //take the first "limit" elements in the vector. If we didn't specify a limit, use 0
+        .take(limit.unwrap_or(0) as usize)
+        .collect()
+}
+
+impl Contract {
+    //used to make sure the marketplace isn't paused before creating sales, buy offers or purchases
+    pub(crate) fn assert_not_paused(&self) {
+        assert!(!self.paused, "Marketplace is paused");
+    }
+
+    //used to make sure an nft contract isn't denied (or missing from the allowlist when it's enabled)
+    pub(crate) fn assert_nft_contract_allowed(&self, nft_contract_id: &AccountId) {
+        assert!(!self.denied_nft_contract_ids.contains(nft_contract_id), "NFT contract is denied: {}", nft_contract_id);
+        assert!(
+            !self.allowlist_enabled || self.allowed_nft_contract_ids.contains(nft_contract_id),
+            "NFT contract is not on the allowlist: {}", nft_contract_id
+        );
+    }
+}
@@ -6,7 +6,22 @@ use crate::*;
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct SaleArgs {
-    pub sale_conditions: SalePriceInYoctoNear
+    //the price of the sale. This is required unless the token is listed as a swap
+    pub sale_conditions: Option<SalePriceInYoctoNear>,
+    //if present, the token is listed as an english auction and the sale conditions are the starting price
+    pub auction: Option<AuctionArgs>,
+    //if present, the sale is priced in this fungible token instead of NEAR
+    pub ft_token_id: Option<FungibleTokenId>,
+    //if present, the sale can't be purchased before this block timestamp (in nanoseconds)
+    pub starts_at: Option<U64>,
+    //if present, the sale can't be purchased after this block timestamp (in nanoseconds) and anyone can remove it
+    pub expires_at: Option<U64>,
+    //if present, only these accounts can purchase the sale. This is used for private sales negotiated off-chain
+    pub reserved_for: Option<Vec<AccountId>>,
+    //if present, the token is listed as a dutch auction and the sale conditions are the starting price
+    pub dutch_auction: Option<DutchAuctionArgs>,
+    //if present, the token is listed as a swap for the requested token instead of for a price
+    pub swap_for: Option<SwapTerms>,
 }
 
 /*
@@ -14,8 +29,11 @@ pub struct SaleArgs {
     called, it will fire a cross contract call to this marketplace and this is the function
     that is invoked. 
 */
-trait NonFungibleTokenApprovalsReceiver {
+pub trait NonFungibleTokenApprovalsReceiver {
     fn nft_on_approve(&mut self, token_id: TokenId, owner_id: AccountId, approval_id: u64, msg: String);
+
+    //called by nft contracts that notify approved accounts when their approval is revoked
+    fn nft_on_revoke(&mut self, token_id: TokenId);
 }
 
 //implementation of the trait
@@ -32,42 +50,129 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
         //this was called via a cross-contract call
         assert_ne!(nft_contract_id, signer_id, "nft_on_approve should only be called via cross-contract call");
         //make sure the owner ID is the signer. 
-        assert_ne!(owner_id, signer_id, "owner_id should be signer_id");
-
-        //we need to enforce that the user has enough storage for 1 EXTRA sale.  
-        //get the storage for a sale. dot 0 converts from U128 to u128
-        let storage_amount = self.storage_minimum_balance().0;
-        //get the total storage paid by the owner
-        let owner_paid_storage = self.storage_deposits.get(&signer_id).unwrap_or(0);
-        //get the storage required which is simply the storage for the number of sales they have + 1 
-        let signer_storage_required = (self.get_supply_by_owner_id(signer_id).0 + 1) as u128 * storage_amount;
-
-        //make sure that the total paid is >= the required storage
-        assert!(
-            owner_paid_storage >= signer_storage_required,
-            "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
-            owner_paid_storage, signer_storage_required / STORAGE_PER_SALE, STORAGE_PER_SALE
-        );
+        assert_eq!(owner_id, signer_id, "owner_id should be signer_id");
+        //make sure the marketplace isn't paused and the nft contract can be traded here
+        self.assert_not_paused();
+        self.assert_nft_contract_allowed(&nft_contract_id);
+
+        //if the owner is adding the token to one of their bundles, it isn't listed on its own
+        if let Ok(args) = near_sdk::serde_json::from_str::<AddToBundleArgs>(&msg) {
+            self.internal_add_to_bundle(nft_contract_id, token_id, owner_id, approval_id, args);
+            return;
+        }
+
+        //the token's approval changed so it's no longer sold as part of a bundle
+        self.internal_remove_bundle_for_token(&nft_contract_id, &token_id);
+
+        //if the owner is accepting a standing buy offer, we transfer the token to the buyer instead of listing it
+        if let Ok(AcceptOfferArgs { accept_offer_id }) = near_sdk::serde_json::from_str(&msg) {
+            self.internal_accept_buy_offer(nft_contract_id, token_id, owner_id, approval_id, accept_offer_id.0);
+            return;
+        }
+
+        //if the owner is accepting a swap listing, the token is exchanged for the listed token instead of being listed
+        if let Ok(AcceptSwapArgs { accept_swap }) = near_sdk::serde_json::from_str(&msg) {
+            self.internal_accept_swap(nft_contract_id, token_id, owner_id, approval_id, accept_swap);
+            return;
+        }
 
         //if all these checks pass we can create the sale conditions object.
         //the sale conditions come from the msg field. The market assumes that the user passed
         //in a proper msg. If they didn't, it panics. 
-        let SaleArgs { sale_conditions } = near_sdk::serde_json::from_str(&msg).expect("Not valid SaleArgs");
+        let SaleArgs { sale_conditions, auction, ft_token_id, starts_at, expires_at, reserved_for, dutch_auction, swap_for } = near_sdk::serde_json::from_str(&msg).expect("Not valid SaleArgs");
+
+        //make sure the sale window is valid. Auctions have their own start and end times
+        if auction.is_some() || dutch_auction.is_some() {
+            assert!(starts_at.is_none() && expires_at.is_none(), "Auctions use their own start and end times");
+            assert!(auction.is_none() || dutch_auction.is_none(), "A sale can't be both an english and a dutch auction");
+        }
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Sale must expire in the future");
+            assert!(starts_at.is_none_or(|starts_at| expires_at.0 > starts_at.0), "Sale must expire after it starts");
+        }
+
+        //a swap listing asks for a token instead of a price so it can't also be priced or auctioned
+        let sale_conditions = if swap_for.is_some() {
+            assert!(
+                sale_conditions.is_none() && ft_token_id.is_none() && auction.is_none() && dutch_auction.is_none(),
+                "Swap listings can't have a price"
+            );
+            U128(0)
+        } else {
+            sale_conditions.expect("Missing sale_conditions")
+        };
+
+        //a private sale must be reserved for a small set of accounts that doesn't include the owner
+        if let Some(reserved_for) = reserved_for.as_ref() {
+            assert!(
+                !reserved_for.is_empty() && reserved_for.len() <= MAX_RESERVED_FOR,
+                "A sale must be reserved for between 1 and {} accounts", MAX_RESERVED_FOR
+            );
+            assert!(!reserved_for.contains(&owner_id), "Cannot reserve a sale for its owner");
+        }
+
+        //sales can only be priced in fungible tokens that are on the marketplace whitelist
+        if let Some(ft_token_id) = ft_token_id.as_ref() {
+            assert!(self.approved_ft_token_ids.contains(ft_token_id), "FT token is not approved: {}", ft_token_id);
+            //bids are held and refunded in NEAR so auctions can't be priced in fungible tokens
+            assert!(auction.is_none(), "Auctions can only be priced in NEAR");
+        }
 
         //create the unique sale ID which is the contract + DELIMITER + token ID
         let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
 
-         //insert the key value pair into the sales map. Key is the unique ID. value is the sale object
-         self.sales.insert(
-            &contract_and_token_id,
-            &Sale {
-                owner_id: owner_id.clone(), //owner of the sale / token
-                approval_id, //approval ID for that token that was given to the market
-                nft_contract_id: nft_contract_id.to_string(), //NFT contract the token was minted on
-                token_id: token_id.clone(), //the actual token ID
-                sale_conditions, //the sale conditions 
-           },
-        );
+        //make sure we don't overwrite an auction that is holding bidders' funds
+        if let Some(existing_sale) = self.sales.get(&contract_and_token_id) {
+            assert!(!existing_sale.auction.is_some_and(|a| a.has_bids()), "Token is in an auction with active bids");
+            //the token is being re-listed so we take the old sale down along with its index entries
+            self.internal_cancel_sale(nft_contract_id.clone(), token_id.clone(), "relisted");
+        }
+
+        //we need to enforce that the user has enough storage for 1 EXTRA sale. This is checked once the old sale
+        //is taken down so that re-listing a token doesn't need a spare storage slot
+        self.assert_storage_available(&signer_id);
+
+        //create the sale object and insert the key value pair into the sales map. Key is the unique ID. value is the sale object
+        let sale = Sale {
+            owner_id: owner_id.clone(), //owner of the sale / token
+            approval_id, //approval ID for that token that was given to the market
+            nft_contract_id: nft_contract_id.to_string(), //NFT contract the token was minted on
+            token_id: token_id.clone(), //the actual token ID
+            sale_conditions, //the sale conditions 
+            auction: auction.map(Auction::new), //the auction state if the token is listed as an auction
+            ft_token_id, //the fungible token the sale is priced in (None for NEAR)
+            starts_at, //when the sale opens for purchases
+            expires_at, //when the sale stops accepting purchases
+            reserved_for, //the accounts that can purchase a private sale
+            dutch_auction: dutch_auction.map(|args| DutchAuction::new(args, sale_conditions.0)), //the price schedule if the token is listed as a dutch auction
+            swap_for, //the token asked for in exchange if the token is listed as a swap
+        };
+        self.sales.insert(&contract_and_token_id, &sale);
+        //add the sale to the price index for the nft contract
+        self.internal_index_price(&sale);
+        //update the listing stats for the nft contract and the seller
+        self.internal_record_listing(&nft_contract_id, &owner_id);
+
+        // Construct the list log as per the events standard.
+        let sale_list_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SaleList(vec![SaleListLog {
+                owner_id: sale.owner_id.to_string(),
+                nft_contract_id: sale.nft_contract_id.clone(),
+                token_id: sale.token_id.clone(),
+                price: sale.sale_conditions,
+                ft_token_id: sale.ft_token_id.as_ref().map(|id| id.to_string()),
+                is_auction: sale.auction.is_some(),
+                reserved_for: sale.reserved_for.as_ref().map(|accounts| accounts.iter().map(|a| a.to_string()).collect()),
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&sale_list_log.to_string());
 
         //Extra functionality that populates collections necessary for the view calls 
          //get the sales by owner ID for the given owner. If there are none, we create a new empty set
@@ -106,6 +211,38 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
         by_nft_contract_id.insert(&token_id);
         //insert the set back into the collection for the given nft contract ID
         self.by_nft_contract_id.insert(&nft_contract_id, &by_nft_contract_id);
+
+        //add the unique sale ID to the set of private sales for every account it's reserved for
+        for account_id in sale.reserved_for.iter().flatten() {
+            let mut by_reserved_for = self.by_reserved_for.get(account_id).unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::ByReservedForInner {
+                        //we get a new unique prefix for the collection by hashing the account
+                        account_id_hash: hash_account_id(account_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            by_reserved_for.insert(&contract_and_token_id);
+            self.by_reserved_for.insert(account_id, &by_reserved_for);
+        }
                                                                    
     }
-}
\ No newline at end of file
+
+    // where we take down the sale because the market can no longer transfer the token
+    fn nft_on_revoke(&mut self, token_id: TokenId) {
+        // get the contract ID which is the predecessor. Only the nft contract can take down its own tokens' sales
+        let nft_contract_id = env::predecessor_account_id();
+
+        //create the unique sale ID which is the contract + DELIMITER + token ID
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+
+        //if there was a sale for the token, we remove it and refund any bidder
+        if self.sales.get(&contract_and_token_id).is_some() {
+            self.internal_cancel_sale(nft_contract_id.clone(), token_id.clone(), "approval_revoked");
+        }
+        //the bundle the token was in can no longer be purchased either
+        self.internal_remove_bundle_for_token(&nft_contract_id, &token_id);
+    }
+}
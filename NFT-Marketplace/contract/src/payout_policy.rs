@@ -0,0 +1,145 @@
+use crate::*;
+use near_sdk::promise_result_as_success;
+
+//royalties and seller shares are expressed in basis points. 10 000 basis points is 100%
+const TOTAL_BASIS_POINTS: u32 = 10_000;
+
+//struct for returning the marketplace's payout policy
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutPolicy {
+    pub max_royalty_basis_points: u32,  //the most that all the royalty accounts together can receive from a sale
+    pub min_seller_basis_points: u32,   //the least that the seller must receive from a sale
+}
+
+#[near_bindgen]
+impl Contract {
+    //Allows the owner to set the limits that the payout returned by nft contracts must respect. Payouts that don't are refunded to the buyer
+    pub fn set_payout_policy(&mut self, max_royalty_basis_points: u32, min_seller_basis_points: u32) {
+        //make sure only the owner can change the payout policy
+        self.assert_owner();
+        assert!(max_royalty_basis_points <= TOTAL_BASIS_POINTS, "Royalties can't be more than {} basis points", TOTAL_BASIS_POINTS);
+        assert!(min_seller_basis_points <= TOTAL_BASIS_POINTS, "Seller share can't be more than {} basis points", TOTAL_BASIS_POINTS);
+        self.max_royalty_basis_points = max_royalty_basis_points;
+        self.min_seller_basis_points = min_seller_basis_points;
+    }
+
+    //Allows the owner to set the royalty schedule (in basis points for every royalty account) that payouts of a verified nft contract are checked against
+    pub fn set_royalty_schedule(&mut self, nft_contract_id: AccountId, royalty: HashMap<AccountId, u32>) {
+        //make sure only the owner can change royalty schedules
+        self.assert_owner();
+        self.internal_set_royalty_schedule(nft_contract_id, royalty);
+    }
+
+    //Allows the owner to stop checking the payouts of an nft contract against a royalty schedule
+    pub fn remove_royalty_schedule(&mut self, nft_contract_id: AccountId) {
+        //make sure only the owner can change royalty schedules
+        self.assert_owner();
+        self.royalty_schedules.remove(&nft_contract_id);
+    }
+
+    /*
+        Allows the owner to cache the royalty schedule of a verified nft contract by querying nft_payout for one of its tokens.
+        The owner of the token is passed in so that their share can be left out of the schedule.
+    */
+    pub fn cache_royalty_schedule(&mut self, nft_contract_id: AccountId, token_id: TokenId, owner_id: AccountId) -> Promise {
+        //make sure only the owner can change royalty schedules
+        self.assert_owner();
+        assert!(self.verified_nft_contract_ids.contains(&nft_contract_id), "NFT contract is not verified: {}", nft_contract_id);
+
+        //ask for the payout of a balance of 10 000 so that every account's share is returned in basis points
+        ext_contract::ext(nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_PAYOUT)
+            .nft_payout(token_id, U128(u128::from(TOTAL_BASIS_POINTS)), MAX_LEN_PAYOUT)
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_ROYALTY_SCHEDULE)
+            .resolve_cache_royalty_schedule(nft_contract_id, owner_id)
+        )
+    }
+
+    //private method used to resolve the promise when calling nft_payout. Returns true if the royalty schedule was cached
+    #[private]
+    pub fn resolve_cache_royalty_schedule(&mut self, nft_contract_id: AccountId, owner_id: AccountId) -> bool {
+        //get the payout returned by the nft contract. If the call failed, the schedule isn't changed
+        let payout = if let Some(payout) = promise_result_as_success()
+            .and_then(|value| near_sdk::serde_json::from_slice::<Payout>(&value).ok())
+        {
+            payout.payout
+        } else {
+            env::log_str("Could not get the payout from the nft contract");
+            return false;
+        };
+
+        //every account except the token owner is a royalty account
+        let royalty = payout
+            .into_iter()
+            .filter(|(account_id, _)| account_id != &owner_id)
+            .map(|(account_id, amount)| (account_id, amount.0 as u32))
+            .collect();
+        self.internal_set_royalty_schedule(nft_contract_id, royalty);
+        true
+    }
+
+    // views
+    //return the limits that the payouts returned by nft contracts must respect
+    pub fn get_payout_policy(&self) -> PayoutPolicy {
+        PayoutPolicy {
+            max_royalty_basis_points: self.max_royalty_basis_points,
+            min_seller_basis_points: self.min_seller_basis_points,
+        }
+    }
+
+    //return the royalty schedule that the payouts of an nft contract are checked against
+    pub fn get_royalty_schedule(&self, nft_contract_id: AccountId) -> Option<HashMap<AccountId, u32>> {
+        self.royalty_schedules.get(&nft_contract_id)
+    }
+}
+
+impl Contract {
+    //internal method for setting the royalty schedule of a verified nft contract
+    fn internal_set_royalty_schedule(&mut self, nft_contract_id: AccountId, royalty: HashMap<AccountId, u32>) {
+        assert!(self.verified_nft_contract_ids.contains(&nft_contract_id), "NFT contract is not verified: {}", nft_contract_id);
+        //make sure the royalties don't add up to more than the whole sale
+        let total: u32 = royalty.values().try_fold(0u32, |total, &bps| total.checked_add(bps)).expect("Royalty overflow");
+        assert!(total <= TOTAL_BASIS_POINTS, "Royalties can't be more than {} basis points", TOTAL_BASIS_POINTS);
+        self.royalty_schedules.insert(&nft_contract_id, &royalty);
+    }
+
+    /*
+        internal method for checking a payout returned by nft_payout against the payout policy. The balance is the
+        price minus the marketplace fee. If the payout is rejected, the reason is returned so it can be logged.
+    */
+    pub(crate) fn internal_check_payout_policy(
+        &self,
+        nft_contract_id: &AccountId,
+        seller_id: &AccountId,
+        balance: Balance,
+        payout: &HashMap<AccountId, U128>,
+    ) -> Result<(), String> {
+        //the share of the sale that goes to the seller, everything else is a royalty
+        let seller_amount = payout.get(seller_id).map(|amount| amount.0).unwrap_or(0);
+        let royalty_amount = balance.saturating_sub(seller_amount);
+
+        if seller_amount * u128::from(TOTAL_BASIS_POINTS) < balance * u128::from(self.min_seller_basis_points) {
+            return Err("seller_share_below_minimum".to_string());
+        }
+        if royalty_amount * u128::from(TOTAL_BASIS_POINTS) > balance * u128::from(self.max_royalty_basis_points) {
+            return Err("royalty_above_maximum".to_string());
+        }
+
+        //the payouts of verified nft contracts can't go to accounts outside of their royalty schedule or pay them more than their share
+        if self.verified_nft_contract_ids.contains(nft_contract_id) {
+            if let Some(schedule) = self.royalty_schedules.get(nft_contract_id) {
+                for (account_id, amount) in payout.iter().filter(|(account_id, _)| *account_id != seller_id) {
+                    let bps = schedule.get(account_id).ok_or_else(|| "royalty_not_in_schedule".to_string())?;
+                    if amount.0 * u128::from(TOTAL_BASIS_POINTS) > balance * u128::from(*bps) {
+                        return Err("royalty_above_schedule".to_string());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use crate::*;
+use near_sdk::promise_result_as_success;
+
+#[near_bindgen]
+impl Contract {
+    /*
+        removes a sale that can no longer be purchased because the token was transferred or the market's approval
+        was revoked outside of the marketplace. Anyone can call this. The nft contract is queried for the token and
+        the sale is only removed if the owner or the market's approval ID no longer match.
+    */
+    pub fn prune_sale(&mut self, nft_contract_id: AccountId, token_id: TokenId) -> Promise {
+        //get the unique sale ID (contract + DELIMITER + token ID)
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //query the nft contract for the token and check it against the sale in the callback
+        ext_contract::ext(nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_TOKEN)
+            .nft_token(token_id.clone())
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_PRUNE_SALE)
+            .resolve_prune_sale(
+                nft_contract_id,
+                token_id,
+                sale.approval_id, //the approval ID is passed in so we don't remove a sale that was re-listed in the meantime
+            )
+        )
+    }
+
+    /*
+        private method used to resolve the promise when calling nft_token. Returns true if the sale was stale and removed.
+        If the nft contract call failed, we can't tell whether the sale is stale so we leave it.
+    */
+    #[private]
+    pub fn resolve_prune_sale(&mut self, nft_contract_id: AccountId, token_id: TokenId, approval_id: u64) -> bool {
+        //get the token returned by the nft contract. If the call failed, we don't touch the sale
+        let token = if let Some(value) = promise_result_as_success() {
+            near_sdk::serde_json::from_slice::<Option<JsonToken>>(&value).unwrap_or(None)
+        } else {
+            env::log_str("Could not get the token from the nft contract");
+            return false;
+        };
+
+        //make sure the sale is still the one we checked
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        let sale = match self.sales.get(&contract_and_token_id) {
+            Some(sale) if sale.approval_id == approval_id => sale,
+            _ => return false,
+        };
+
+        //the sale is stale if the token no longer exists, changed owner or the market's approval was revoked or replaced
+        let is_stale = token.is_none_or(|token| {
+            token.owner_id != sale.owner_id
+                || token.approved_account_ids.get(&env::current_account_id()) != Some(&sale.approval_id)
+        });
+
+        if is_stale {
+            //removing the sale frees up the seller's storage slot so they can withdraw it
+            self.internal_cancel_sale(nft_contract_id, token_id, "stale");
+            env::log_str(&format!("Pruned stale sale {}", contract_and_token_id));
+        }
+
+        is_stale
+    }
+}
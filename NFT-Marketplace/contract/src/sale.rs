@@ -9,7 +9,84 @@ pub struct Sale {
     pub approval_id: u64,                           //market contract's approval ID to transfer the token on behalf of the owner
     pub nft_contract_id: String,                    //nft contract where the token was minted
     pub token_id: String,                           //actual token ID for sale
-    pub sale_conditions: SalePriceInYoctoNear       //sale price in yoctoNEAR that the token is listed for
+    pub sale_conditions: SalePriceInYoctoNear,      //sale price in yoctoNEAR that the token is listed for (starting price for auctions)
+    pub auction: Option<Auction>,                   //auction state if the token is listed as an english auction
+    pub ft_token_id: Option<FungibleTokenId>,       //fungible token the sale is priced in. If None, the sale is priced in NEAR
+    pub starts_at: Option<U64>,                     //block timestamp (in nanoseconds) before which the sale can't be purchased
+    pub expires_at: Option<U64>,                    //block timestamp (in nanoseconds) after which the sale can't be purchased and can be swept
+    pub reserved_for: Option<Vec<AccountId>>,       //if present, only these accounts can purchase (or bid on) the sale
+    pub dutch_auction: Option<DutchAuction>,        //price schedule if the token is listed as a dutch auction (sale conditions are the starting price)
+    pub swap_for: Option<SwapTerms>,                //token asked for in exchange if the token is listed as a swap instead of for a price
+}
+
+//the details of a purchase that are passed along to the callbacks that check the payout and resolve the purchase
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseContext {
+    pub nft_contract_id: AccountId,                 //nft contract where the token was minted
+    pub token_id: TokenId,                          //actual token ID being purchased
+    pub approval_id: u64,                           //market contract's approval ID to transfer the token on behalf of the owner
+    pub seller_id: AccountId,                       //owner of the token, used to check the payout against the payout policy
+    pub fee: U128,                                  //marketplace fee taken from the price before royalties
+    pub ft_token_id: Option<FungibleTokenId>,       //currency the buyer paid in so that payouts and refunds are sent in the same token
+}
+
+//a purchase whose payout was checked against the payout policy and that is waiting for its token to be transferred
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CheckedPurchase {
+    pub price: U128,                                //price the buyer payed
+    pub payout: HashMap<AccountId, U128>,           //payout returned by nft_payout that will be paid out once the token is transferred
+    pub context: PurchaseContext,                   //details of the purchase
+}
+
+impl PurchaseContext {
+    //returns the GAS needed to pay out the purchase in resolve_purchase. FT payouts need GAS for an ft_transfer per account
+    pub(crate) fn gas_for_resolve_purchase(&self) -> Gas {
+        if self.ft_token_id.is_some() {
+            GAS_FOR_RESOLVE_PURCHASE
+        } else {
+            GAS_FOR_RESOLVE_NEAR_PURCHASE
+        }
+    }
+}
+
+impl Sale {
+    //returns true if the sale's start time has passed (or it doesn't have one)
+    pub(crate) fn has_started(&self) -> bool {
+        //dutch auctions open when their price starts dropping
+        let starts_at = self.starts_at.as_ref().or(self.dutch_auction.as_ref().map(|d| &d.starts_at));
+        starts_at.is_none_or(|starts_at| env::block_timestamp() >= starts_at.0)
+    }
+
+    //returns true if the sale has passed its expiry
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0)
+    }
+
+    //returns true if the sale is in its purchase window (it has started and hasn't expired)
+    pub(crate) fn is_open(&self) -> bool {
+        self.has_started() && !self.is_expired()
+    }
+
+    //make sure the sale can currently be purchased
+    pub(crate) fn assert_purchase_window(&self) {
+        assert!(self.has_started(), "Sale has not started");
+        assert!(!self.is_expired(), "Sale has expired");
+    }
+
+    //returns the price the sale can currently be purchased for. Only dutch auction prices change over time
+    pub(crate) fn current_price(&self) -> Balance {
+        match &self.dutch_auction {
+            Some(dutch_auction) => dutch_auction.current_price(self.sale_conditions.0),
+            None => self.sale_conditions.0,
+        }
+    }
+
+    //returns true if the account can purchase the sale. Sales that aren't reserved can be purchased by anyone
+    pub(crate) fn is_reserved_for(&self, account_id: &AccountId) -> bool {
+        self.reserved_for.as_ref().is_none_or(|reserved_for| reserved_for.contains(account_id))
+    }
 }
 
 #[near_bindgen]
@@ -19,12 +96,8 @@ impl Contract {
     pub fn remove_sale(&mut self, nft_contract_id: AccountId, token_id: String) {
         //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
         assert_one_yocto();
-        //get the sale object as the return value from removing the sale internally
-        let sale = self.internal_remove_sale(nft_contract_id.into(), token_id);
-        //get the predecessor of the call and make sure they're the owner of the sale
-        let owner_id = env::predecessor_account_id();
-        //if this fails, the remove sale will revert
-        assert_eq!(owner_id, sale.owner_id, "Must be sale owner");
+        //remove the sale on behalf of the caller. This makes sure they're the owner of the sale
+        self.internal_seller_remove_sale(&env::predecessor_account_id(), nft_contract_id, token_id);
     }
 
     //updates the price for a sale on the market
@@ -32,29 +105,35 @@ impl Contract {
     pub fn update_price(&mut self, nft_contract_id: AccountId, token_id: String, price: U128) {
         //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
         assert_one_yocto();
+        //update the price on behalf of the caller. This makes sure they're the owner of the sale
+        self.internal_update_price(&env::predecessor_account_id(), nft_contract_id, token_id, price);
+    }
 
-        //create the unique sale ID from the nft contract and token
-        let contract_id: AccountId = nft_contract_id.into();
-        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMITER, token_id);
-
-        //get the sale object from the unique sale ID. If there is no token, panic. 
-        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
-
-        //assert that the caller of the function is the sale owner
-        assert_eq!(env::predecessor_account_id(), sale.owner_id, "Must be sale owner");
+    //removes listings that have expired. Anyone can call this. Sales that don't exist or haven't expired are skipped.
+    //removing the sales frees up the sellers' storage. Returns the number of sales removed
+    pub fn remove_expired_sales(&mut self, sales: Vec<SaleKey>) -> u32 {
+        assert!(sales.len() <= MAX_BATCH_SIZE, "Cannot remove more than {} sales at once", MAX_BATCH_SIZE);
 
-        //set the sale conditions equal to the passed in price
-        sale.sale_conditions = price;
-        //insert the sale back into the map for the unique sale ID
-        self.sales.insert(&contract_and_token_id, &sale);
+        let mut removed = 0;
+        for SaleKey { nft_contract_id, token_id } in sales {
+            let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+            if self.sales.get(&contract_and_token_id).is_some_and(|sale| sale.is_expired()) {
+                self.internal_cancel_sale(nft_contract_id, token_id, "expired");
+                removed += 1;
+            }
+        }
+        removed
     }
 
     //place an offer on a specific sale. The sale will go through as long as your deposit is greater than or equal to the list price
+    //for auctions, the offer is placed as a bid and the sale goes through when the auction is settled
     #[payable]
     pub fn offer(&mut self, nft_contract_id: AccountId, token_id: String) {
         //get the attached deposit and make sure it's greater than 0
         let deposit = env::attached_deposit();
         assert!(deposit > 0, "Attached deposit must be greater than 0");
+        //make sure the marketplace isn't paused
+        self.assert_not_paused();
 
         //convert the nft_contract_id from a AccountId to an AccountId
         let contract_id: AccountId = nft_contract_id.into();
@@ -67,13 +146,38 @@ impl Contract {
         //get the buyer ID which is the person who called the function and make sure they're not the owner of the sale
         let buyer_id = env::predecessor_account_id();
         assert_ne!(sale.owner_id, buyer_id, "Cannot bid on your own sale");
+        //make sure the sale has started and hasn't expired
+        sale.assert_purchase_window();
+
+        //private sales can only be purchased by the accounts they're reserved for
+        assert!(sale.is_reserved_for(&buyer_id), "Sale is reserved for other accounts");
+        //swap listings can only be accepted with a token through nft_approve
+        assert!(sale.swap_for.is_none(), "Sale is a swap listing, accept it by approving a requested token");
+
+        //sales priced in fungible tokens have to be purchased with ft_transfer_call on the FT contract
+        assert!(sale.ft_token_id.is_none(), "Sale is priced in {:?}, purchase it with ft_transfer_call", sale.ft_token_id);
+
+        //if the sale is an auction, the deposit is placed as a bid instead of buying the token outright
+        if sale.auction.is_some() {
+            self.internal_place_bid(&contract_and_token_id, sale, buyer_id, deposit);
+            return;
+        }
 
-        //get the u128 price of the token (dot 0 converts from U128 to u128)
-        let price = sale.sale_conditions.0;
+        //get the u128 price of the token. Dutch auction prices drop over time so they're computed at the current block timestamp
+        let price = sale.current_price();
 
         //make sure the deposit is greater than the price
         assert!(deposit >= price, "Attached deposit must be greater than or equal to the current price: {:?}", price);
 
+        //a dutch auction is purchased for exactly its current price and any overpayment is refunded
+        if sale.dutch_auction.is_some() {
+            if deposit > price {
+                Promise::new(buyer_id.clone()).transfer(deposit - price);
+            }
+            self.process_purchase(contract_id, token_id, U128(price), buyer_id);
+            return;
+        }
+
         //process the purchase (which will remove the sale, transfer and get the payout from the nft contract, and then distribute royalties) 
         self.process_purchase(
             contract_id,
@@ -84,52 +188,76 @@ impl Contract {
     }
 
     //private function used when a sale is purchased. 
-    //this will remove the sale, transfer and get the payout from the nft contract, and then distribute royalties
+    //this will remove the sale, check the payout from the nft contract, transfer the token and then distribute royalties
     #[private]
     pub fn process_purchase(&mut self, nft_contract_id: AccountId, token_id: String, price: U128, buyer_id: AccountId) -> Promise {
          //get the sale object by removing the sale
          let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
 
-         //initiate a cross contract call to the nft contract. This will transfer the token to the buyer and return
-         //a payout object used for the market to distribute funds to the appropriate accounts.
-         ext_contract::ext(nft_contract_id)
-             // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
-             .with_attached_deposit(1)
-             .with_static_gas(GAS_FOR_NFT_TRANSFER)
-             .nft_transfer_payout(
-                 buyer_id.clone(), //purchaser (person to transfer the NFT to)
-                 token_id, //token ID to transfer
-                 sale.approval_id, //market contract's approval ID in order to transfer the token on behalf of the owner
-             "payout from market".to_string(), //memo (to include some context)
-             /*
-                 the price that the token was purchased for. This will be used in conjunction with the royalty percentages
-                 for the token in order to determine how much money should go to which account. 
-             */
-             price,
-             10, //the maximum amount of accounts the market can payout at once (this is limited by GAS)
-             )
-         //after the transfer payout has been initiated, we resolve the promise by calling our own resolve_purchase function. 
-         //resolve purchase will take the payout object returned from the nft_transfer_payout and actually pay the accounts
-         .then(
-             // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
-             Self::ext(env::current_account_id())
-             .with_static_gas(GAS_FOR_RESOLVE_PURCHASE)
-             .resolve_purchase(
-                 buyer_id, //the buyer and price are passed in incase something goes wrong and we need to refund the buyer
-                 price,
-             )
-         )
+         //transfer the token to the buyer and distribute the payout in whichever currency the sale was priced in
+         let context = PurchaseContext {
+             nft_contract_id,
+             token_id,
+             approval_id: sale.approval_id,
+             seller_id: sale.owner_id,
+             fee: U128(self.internal_fee_for(price.0)),
+             ft_token_id: sale.ft_token_id,
+         };
+         self.internal_transfer_payout(context, price, buyer_id)
+    }
+
+    /*
+        private method used to resolve the promise when calling nft_payout. The payout is checked to make sure it adds up and
+        respects the marketplace's payout policy. If it does, the token is transferred with nft_transfer_payout and the checked
+        payout is passed on to resolve_purchase. If it doesn't, the token is never transferred and the buyer is refunded.
+    */
+    #[private]
+    pub fn resolve_purchase_payout(&mut self, buyer_id: AccountId, price: U128, context: PurchaseContext) -> PromiseOrValue<U128> {
+        //the payout is the result of the nft_payout call
+        match self.internal_check_payout(promise_result_as_success(), price, &context) {
+            Ok(payout) => PromiseOrValue::Promise(
+                //transfer the token to the buyer now that we know the payout is valid
+                self.internal_nft_transfer_payout(&context, buyer_id.clone(), price)
+                .then(
+                    // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
+                    Self::ext(env::current_account_id())
+                    .with_static_gas(context.gas_for_resolve_purchase())
+                    .resolve_purchase(buyer_id, CheckedPurchase { price, payout, context })
+                )
+            ),
+            Err(reason) => {
+                //the token stays with the seller so we refund the buyer for the price they payed
+                self.internal_refund_purchase(buyer_id, price, &context, reason);
+                PromiseOrValue::Value(price)
+            }
+        }
     }
 
      /*
-        private method used to resolve the promise when calling nft_transfer_payout. This will take the payout object and 
-        check to see if it's authentic and there's no problems. If everything is fine, it will pay the accounts. If there's a problem,
-        it will refund the buyer for the price. 
+        private method used to resolve the promise when calling nft_transfer_payout. If the token was transferred, the payout
+        that was checked in resolve_purchase_payout is paid out. If the transfer failed, the buyer is refunded for the price.
     */
     #[private]
-    pub fn resolve_purchase(&mut self, buyer_id: AccountId, price: U128) -> U128 {
-        // checking for payout information returned from the nft_transfer_payout method
-        let payout_option = promise_result_as_success().and_then(|value| {
+    pub fn resolve_purchase(&mut self, buyer_id: AccountId, purchase: CheckedPurchase) -> U128 {
+        //the result of the nft_transfer_payout call
+        self.internal_resolve_purchase(promise_result_as_success(), buyer_id, purchase)
+    }
+}
+
+impl Contract {
+    /*
+        internal method for checking the result of nft_payout. The payout must add up to the price minus the marketplace fee,
+        pay at most MAX_LEN_PAYOUT accounts and respect the payout policy for the nft contract. If the payout is rejected,
+        the reason is returned so it can be logged.
+    */
+    pub(crate) fn internal_check_payout(
+        &self,
+        result: Option<Vec<u8>>,
+        price: U128,
+        context: &PurchaseContext,
+    ) -> Result<HashMap<AccountId, U128>, String> {
+        // checking for payout information returned from the nft_payout method
+        let payout_option = result.and_then(|value| {
             //if we set the payout_option to None, that means something went wrong and we should refund the buyer
             near_sdk::serde_json::from_slice::<Payout>(&value)
                 //converts the result to an optional value
@@ -137,14 +265,14 @@ impl Contract {
                 //returns None if the none. Otherwise executes the following logic
                 .and_then(|payout_object| {
                     //we'll check if length of the payout object is > 10 or it's empty. In either case, we return None
-                    if payout_object.payout.len() > 10 || payout_object.payout.is_empty() {
+                    if payout_object.payout.len() > MAX_LEN_PAYOUT as usize || payout_object.payout.is_empty() {
                         env::log_str("Cannot have more than 10 royalties");
                         None
                     
                     //if the payout object is the correct length, we move forward
                     } else {
-                        //we'll keep track of how much the nft contract wants us to payout. Starting at the full price payed by the buyer
-                        let mut remainder = price.0;
+                        //we'll keep track of how much the nft contract wants us to payout. Starting at the price payed by the buyer minus the marketplace fee
+                        let mut remainder = price.0 - context.fee.0;
                         
                         //loop through the payout and subtract the values from the remainder. 
                         for &value in payout_object.payout.values() {
@@ -165,24 +293,162 @@ impl Contract {
                 })
         });
 
-        // if the payout option was some payout, we set this payout variable equal to that some payout
-        let payout = if let Some(payout_option) = payout_option {
-            payout_option
-        //if the payout option was None, we refund the buyer for the price they payed and return
+        //a payout that adds up is also checked against the marketplace's payout policy for the nft contract
+        match payout_option {
+            Some(payout) => self
+                .internal_check_payout_policy(&context.nft_contract_id, &context.seller_id, price.0 - context.fee.0, &payout)
+                .map(|_| payout),
+            None => Err("invalid_payout".to_string()),
+        }
+    }
+
+    //internal method for refunding the buyer of a purchase that was rejected. The reason is logged with the failed purchase
+    pub(crate) fn internal_refund_purchase(&self, buyer_id: AccountId, price: U128, context: &PurchaseContext, reason: String) {
+        // Construct the failed purchase log as per the events standard.
+        let sale_purchase_failed_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SalePurchaseFailed(vec![SalePurchaseFailedLog {
+                buyer_id: buyer_id.to_string(),
+                nft_contract_id: context.nft_contract_id.to_string(),
+                token_id: context.token_id.clone(),
+                price,
+                ft_token_id: context.ft_token_id.as_ref().map(|id| id.to_string()),
+                // Why the purchase was rejected.
+                reason,
+            }]),
+        };
+        // Log the serialized json.
+        env::log_str(&sale_purchase_failed_log.to_string());
+
+        transfer_funds(context.ft_token_id.as_ref(), buyer_id, u128::from(price));
+    }
+
+    /*
+        internal method for resolving a purchase from the result of nft_transfer_payout. If the token was transferred, the
+        checked payout is paid and the sale is recorded. Otherwise the buyer is refunded for the price. Returns the price.
+    */
+    pub(crate) fn internal_resolve_purchase(&mut self, result: Option<Vec<u8>>, buyer_id: AccountId, purchase: CheckedPurchase) -> U128 {
+        let CheckedPurchase { price, payout, context } = purchase;
+
+        //if the transfer failed, the token is still with the seller so we refund the buyer for the price they payed and return
+        let transferred_payout = if let Some(value) = result {
+            near_sdk::serde_json::from_slice::<Payout>(&value).ok().map(|payout_object| payout_object.payout)
         } else {
-            Promise::new(buyer_id).transfer(u128::from(price));
+            self.internal_refund_purchase(buyer_id, price, &context, "transfer_failed".to_string());
             // leave function and return the price that was refunded
             return price;
         };
 
-        // NEAR payouts
-        for (receiver_id, amount) in payout {
-            Promise::new(receiver_id).transfer(amount.0);
+        //the token has already been transferred so the payout that was checked is paid out. If the nft contract returned a different
+        //payout from nft_transfer_payout than from nft_payout, it's ignored
+        if transferred_payout.as_ref() != Some(&payout) {
+            env::log_str("Payout from nft_transfer_payout doesn't match nft_payout, paying out the checked payout");
+        }
+
+        // NEAR or FT payouts depending on what the sale was priced in
+        for (receiver_id, amount) in payout.iter() {
+            transfer_funds(context.ft_token_id.as_ref(), receiver_id.clone(), amount.0);
         }
 
+        let PurchaseContext { nft_contract_id, token_id, seller_id, fee, ft_token_id, .. } = context;
+        //add the fee to the treasury and record the sale's volume for the nft contract
+        self.internal_record_sale(&nft_contract_id, ft_token_id.as_ref(), price.0, fee.0);
+        //update the sales stats for the nft contract and the seller
+        self.internal_record_sale_stats(&nft_contract_id, &seller_id, ft_token_id.as_ref(), price.0);
+        //add the sale to the recent sales of the nft contract
+        self.internal_add_sale_history(&nft_contract_id, SaleRecord {
+            buyer_id: buyer_id.clone(),
+            token_id: token_id.clone(),
+            price,
+            ft_token_id: ft_token_id.clone(),
+            sold_at: U64(env::block_timestamp()),
+        });
+
+        // Construct the purchase log as per the events standard.
+        let sale_purchase_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SalePurchase(vec![SalePurchaseLog {
+                buyer_id: buyer_id.to_string(),
+                nft_contract_id: nft_contract_id.to_string(),
+                token_id,
+                price,
+                ft_token_id: ft_token_id.map(|id| id.to_string()),
+                fee,
+                // The amount paid to the seller and every royalty account.
+                payout: payout.into_iter().map(|(account_id, amount)| (account_id.to_string(), amount)).collect(),
+            }]),
+        };
+        // Log the serialized json.
+        env::log_str(&sale_purchase_log.to_string());
+
         //return the price payout out
         price
     }
+
+    //internal method for a seller taking down one of their sales. Panics if the caller isn't the sale owner
+    pub(crate) fn internal_seller_remove_sale(&mut self, owner_id: &AccountId, nft_contract_id: AccountId, token_id: TokenId) {
+        //get the sale object from the unique sale ID. If there is no sale, panic. 
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        //make sure the caller is the owner of the sale
+        assert_eq!(owner_id, &sale.owner_id, "Must be sale owner");
+        //bidders' funds are held by the market so an auction can't be taken down once someone has bid
+        assert!(!sale.auction.is_some_and(|a| a.has_bids()), "Cannot remove an auction with active bids");
+
+        self.internal_cancel_sale(nft_contract_id, token_id, "removed_by_owner");
+    }
+
+    //internal method for a seller updating the price of one of their sales. Panics if the caller isn't the sale owner
+    pub(crate) fn internal_update_price(&mut self, owner_id: &AccountId, nft_contract_id: AccountId, token_id: TokenId, price: U128) {
+        //create the unique sale ID from the nft contract and token
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+
+        //get the sale object from the unique sale ID. If there is no token, panic. 
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //assert that the caller of the function is the sale owner
+        assert_eq!(owner_id, &sale.owner_id, "Must be sale owner");
+        //the starting price of an auction can't change once someone has bid
+        assert!(!sale.auction.as_ref().is_some_and(|a| a.has_bids()), "Cannot update the price of an auction with active bids");
+        //the price schedule of a dutch auction is fixed when it's listed
+        assert!(sale.dutch_auction.is_none(), "Cannot update the price of a dutch auction");
+        //swap listings ask for a token instead of a price
+        assert!(sale.swap_for.is_none(), "Cannot update the price of a swap listing");
+
+        //set the sale conditions equal to the passed in price and move the sale in the price index
+        self.internal_unindex_price(&sale);
+        let old_price = std::mem::replace(&mut sale.sale_conditions, price);
+        self.internal_index_price(&sale);
+        //insert the sale back into the map for the unique sale ID
+        self.sales.insert(&contract_and_token_id, &sale);
+
+        // Construct the update price log as per the events standard.
+        let sale_update_price_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SaleUpdatePrice(vec![SaleUpdatePriceLog {
+                owner_id: sale.owner_id.to_string(),
+                nft_contract_id: sale.nft_contract_id,
+                token_id: sale.token_id,
+                old_price,
+                new_price: price,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&sale_update_price_log.to_string());
+    }
 }
 
 //this is the cross contract call that we call on our own contract. 
@@ -193,9 +459,16 @@ impl Contract {
 */
 #[ext_contract(ext_self)]
 trait ExtSelf {
-    fn resolve_purchase(
+    fn resolve_purchase_payout(
         &mut self,
         buyer_id: AccountId,
         price: U128,
+        context: PurchaseContext,
     ) -> Promise;
-}
\ No newline at end of file
+
+    fn resolve_purchase(
+        &mut self,
+        buyer_id: AccountId,
+        purchase: CheckedPurchase,
+    ) -> Promise;
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use std::ops::Bound;
 
 #[near_bindgen]
 impl Contract {
@@ -113,10 +114,118 @@ impl Contract {
             .collect()
     }
 
+    //returns the number of private sales reserved for a given account (result is a string)
+    pub fn get_supply_by_reserved_for(
+        &self,
+        account_id: AccountId,
+    ) -> U64 {
+        //if there was some set, return it's length. Otherwise return 0
+        U64(self.by_reserved_for.get(&account_id).map_or(0, |by_reserved_for| by_reserved_for.len()))
+    }
+
+    //returns paginated private sale objects that a given account can purchase. (result is a vector of sales)
+    pub fn get_sales_by_reserved_for(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Sale> {
+        //get the set of unique sale IDs reserved for the given account ID. If there wasn't a set, return an empty vector
+        let sales = if let Some(by_reserved_for) = self.by_reserved_for.get(&account_id) {
+            by_reserved_for
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the unique sale IDs
+        sales.as_vector().iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 0
+            .take(limit.unwrap_or(0) as usize)
+            //we'll map the unique sale IDs into Sale objects
+            .map(|contract_and_token_id| self.sales.get(&contract_and_token_id).unwrap())
+            .collect()
+    }
+
     //get a sale information for a given unique sale ID (contract + DELIMITER + token ID)
     pub fn get_sale(&self, nft_contract_token: ContractAndTokenId) -> Option<Sale> {
         //try and get the sale object for the given unique sale ID. Will return an option since
         //we're not guaranteed that the unique sale ID passed in will be valid.
         self.sales.get(&nft_contract_token)
     }
-}
\ No newline at end of file
+
+    /*
+        returns paginated fixed price NEAR sales for a given nft contract ordered by price. Sales priced in fungible
+        tokens and auctions aren't included, nor are sales that haven't started yet or have expired. Optionally filter
+        by a price range (both inclusive) and sort from the most expensive sale by passing in descending.
+    */
+    pub fn get_sales_by_price(
+        &self,
+        nft_contract_id: AccountId,
+        min_price: Option<U128>,
+        max_price: Option<U128>,
+        descending: Option<bool>,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Sale> {
+        //get the price index for the given contract ID. If there isn't one, return an empty vector
+        let by_price = if let Some(by_price) = self.by_price.get(&nft_contract_id) {
+            by_price
+        } else {
+            return vec![];
+        };
+
+        let min_price = min_price.map(|p| p.0).unwrap_or(0);
+        let max_price = max_price.map(|p| p.0).unwrap_or(u128::MAX);
+        if min_price > max_price {
+            return vec![];
+        }
+
+        //the index is ordered by (price, token ID). An empty token ID is lower than every other token ID with the same price
+        let keys: Box<dyn Iterator<Item = ((Balance, TokenId), ())>> = if descending.unwrap_or(false) {
+            //start just below the first key above the max price and stop once we're under the min price
+            let iter = match max_price.checked_add(1) {
+                Some(above_max) => Box::new(by_price.iter_rev_from((above_max, String::new()))) as Box<dyn Iterator<Item = _>>,
+                None => Box::new(by_price.iter_rev()),
+            };
+            Box::new(iter.take_while(move |((price, _), _)| *price >= min_price))
+        } else {
+            Box::new(
+                by_price
+                    .range((Bound::Included((min_price, String::new())), Bound::Unbounded))
+                    .take_while(move |((price, _), _)| *price <= max_price),
+            )
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        keys
+            //we'll map the token IDs into Sale objects by passing in the unique sale ID (contract + DELIMITER + token ID)
+            .map(|((_, token_id), _)| self.sales.get(&format!("{}{}{}", nft_contract_id, DELIMITER, token_id)).unwrap())
+            //skip the sales that can't be purchased right now
+            .filter(|sale| sale.is_open())
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 0
+            .take(limit.unwrap_or(0) as usize)
+            .collect()
+    }
+
+    //get the lowest fixed price NEAR sale price for a given nft contract that can be purchased right now. Returns None if there's
+    //no such sale among the MAX_FLOOR_SCAN cheapest sales
+    pub fn get_floor_price(&self, nft_contract_id: AccountId) -> Option<U128> {
+        let by_price = self.by_price.get(&nft_contract_id)?;
+        //walk up from the cheapest sale, skipping the ones that haven't started yet or have expired
+        let floor_price = by_price
+            .iter()
+            .take(MAX_FLOOR_SCAN)
+            .find(|((_, token_id), _)| self.sales.get(&format!("{}{}{}", nft_contract_id, DELIMITER, token_id)).unwrap().is_open())
+            .map(|((price, _), _)| U128(price));
+        floor_price
+    }
+}
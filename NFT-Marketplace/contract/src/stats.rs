@@ -0,0 +1,111 @@
+use crate::*;
+
+//struct that keeps track of the lifetime listing and sales activity of an nft contract or a seller
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct MarketStats {
+    pub listed_count: u64,                          //number of times a token or series was listed
+    pub sold_count: u64,                            //number of completed sales (in NEAR or fungible tokens)
+    pub volume: Balance,                            //total yoctoNEAR paid by buyers
+    pub last_sale_price: Option<Balance>,           //price in yoctoNEAR of the most recent NEAR sale
+    pub highest_sale_price: Option<Balance>,        //highest price in yoctoNEAR ever paid in a NEAR sale
+}
+
+//the stats returned by the views. Prices are in yoctoNEAR since sales in different fungible tokens can't be compared
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonMarketStats {
+    pub listed_count: U64,
+    pub active_listings: U64,
+    pub sold_count: U64,
+    pub volume: U128,
+    pub last_sale_price: Option<U128>,
+    pub highest_sale_price: Option<U128>,
+    pub floor_price: Option<U128>,
+}
+
+impl MarketStats {
+    //converts the stored stats into the view format with the current listings and floor price
+    fn to_json(&self, active_listings: u64, floor_price: Option<Balance>) -> JsonMarketStats {
+        JsonMarketStats {
+            listed_count: U64(self.listed_count),
+            active_listings: U64(active_listings),
+            sold_count: U64(self.sold_count),
+            volume: U128(self.volume),
+            last_sale_price: self.last_sale_price.map(U128),
+            highest_sale_price: self.highest_sale_price.map(U128),
+            floor_price: floor_price.map(U128),
+        }
+    }
+
+    //adds a completed sale to the stats. Only NEAR sales count towards the volume and prices
+    fn record_sale(&mut self, ft_token_id: Option<&FungibleTokenId>, price: Balance) {
+        self.sold_count += 1;
+        if ft_token_id.is_none() {
+            self.volume += price;
+            self.last_sale_price = Some(price);
+            self.highest_sale_price = Some(self.highest_sale_price.map_or(price, |highest| highest.max(price)));
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // views
+    //returns the listing and sales stats for a given nft contract. The floor is the cheapest public fixed price NEAR sale
+    pub fn get_stats_by_nft_contract_id(&self, nft_contract_id: AccountId) -> JsonMarketStats {
+        let active_listings = self.get_supply_by_nft_contract_id(nft_contract_id.clone()).0;
+        let floor_price = self.get_floor_price(nft_contract_id.clone()).map(|price| price.0);
+        self.stats_by_nft_contract_id
+            .get(&nft_contract_id)
+            .unwrap_or_default()
+            .to_json(active_listings, floor_price)
+    }
+
+    //returns the listing and sales stats for a given seller. The floor is the seller's cheapest public fixed price NEAR sale
+    //among their MAX_FLOOR_SCAN cheapest sales
+    pub fn get_stats_by_seller_id(&self, account_id: AccountId) -> JsonMarketStats {
+        let active_listings = self.by_owner_id.get(&account_id).map_or(0, |by_owner_id| by_owner_id.len());
+        //walk up from the seller's cheapest sale, skipping the ones that haven't started yet or have expired
+        let floor_price = self.by_price_by_owner_id.get(&account_id).and_then(|by_seller_price| {
+            by_seller_price
+                .iter()
+                .take(MAX_FLOOR_SCAN)
+                .find(|((_, contract_and_token_id), _)| self.sales.get(contract_and_token_id).unwrap().is_open())
+                .map(|((price, _), _)| price)
+        });
+        self.stats_by_seller_id
+            .get(&account_id)
+            .unwrap_or_default()
+            .to_json(active_listings, floor_price)
+    }
+}
+
+impl Contract {
+    //internal method called when a token or series is listed. This increases the listed count for the nft contract and the seller
+    pub(crate) fn internal_record_listing(&mut self, nft_contract_id: &AccountId, seller_id: &AccountId) {
+        let mut collection_stats = self.stats_by_nft_contract_id.get(nft_contract_id).unwrap_or_default();
+        collection_stats.listed_count += 1;
+        self.stats_by_nft_contract_id.insert(nft_contract_id, &collection_stats);
+
+        let mut seller_stats = self.stats_by_seller_id.get(seller_id).unwrap_or_default();
+        seller_stats.listed_count += 1;
+        self.stats_by_seller_id.insert(seller_id, &seller_stats);
+    }
+
+    //internal method called once a sale has been paid out. This adds the sale to the stats of the nft contract and the seller
+    pub(crate) fn internal_record_sale_stats(
+        &mut self,
+        nft_contract_id: &AccountId,
+        seller_id: &AccountId,
+        ft_token_id: Option<&FungibleTokenId>,
+        price: Balance,
+    ) {
+        let mut collection_stats = self.stats_by_nft_contract_id.get(nft_contract_id).unwrap_or_default();
+        collection_stats.record_sale(ft_token_id, price);
+        self.stats_by_nft_contract_id.insert(nft_contract_id, &collection_stats);
+
+        let mut seller_stats = self.stats_by_seller_id.get(seller_id).unwrap_or_default();
+        seller_stats.record_sale(ft_token_id, price);
+        self.stats_by_seller_id.insert(seller_id, &seller_stats);
+    }
+}
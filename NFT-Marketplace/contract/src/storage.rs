@@ -0,0 +1,194 @@
+use crate::*;
+
+//the storage balance of an account as per the storage management standard (NEP-145)
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,        //total yoctoNEAR the account has deposited for storage
+    pub available: U128,    //yoctoNEAR that isn't used by the account's sales and buy offers and can be withdrawn
+}
+
+//the minimum (and optional maximum) storage balance an account can have
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/*
+    trait for the storage management standard (NEP-145). Accounts pay for the storage of their sales and buy offers
+    on the marketplace by depositing STORAGE_PER_SALE for every entry they want to have up at the same time.
+*/
+pub trait StorageManagement {
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance;
+
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance;
+
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    //Allows users to deposit storage. This is to cover the cost of storing sale objects on the contract
+    //Optional account ID is to users can pay for storage for other people.
+    #[payable]
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance {
+        // get the account ID to pay for storage for. If we didn't specify an account ID, we simply use the caller of the function
+        let storage_account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+
+        //get the deposit value which is how much the user wants to add to their storage
+        let deposit = env::attached_deposit();
+
+        //get the balance of the account. If the account isn't registered yet, the deposit must cover the minimum storage for a sale
+        let balance = match self.storage_deposits.get(&storage_account_id) {
+            Some(balance) => {
+                //an account that is already registered gets its whole deposit back when only registering
+                if registration_only.unwrap_or(false) {
+                    if deposit > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(deposit);
+                    }
+                    balance
+                } else {
+                    balance + deposit
+                }
+            }
+            None => {
+                assert!(deposit >= STORAGE_PER_SALE, "Require minimum deposit of {}", STORAGE_PER_SALE);
+                //when only registering, anything above the minimum is refunded
+                if registration_only.unwrap_or(false) {
+                    let refund = deposit - STORAGE_PER_SALE;
+                    if refund > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(refund);
+                    }
+                    STORAGE_PER_SALE
+                } else {
+                    deposit
+                }
+            }
+        };
+
+        //insert the balance back into the map for that account ID
+        self.storage_deposits.insert(&storage_account_id, &balance);
+        self.internal_storage_balance(&storage_account_id, balance)
+    }
+
+    //Allows users to withdraw any excess storage that they're not using. Say Bob pays 0.01N for 1 sale
+    //Alice then buys Bob's token. This means bob has paid 0.01N for a sale that's no longer on the marketplace
+    //Bob could then withdraw this 0.01N back into his account. If no amount is passed in, all the available storage is withdrawn
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        //this will redirect them to the NEAR wallet (or requires a full access key).
+        assert_one_yocto();
+
+        //the account to withdraw storage to is always the function caller
+        let owner_id = env::predecessor_account_id();
+        let balance = self.storage_deposits.get(&owner_id).unwrap_or_else(|| panic!("The account {} is not registered", owner_id));
+
+        //the excess that can be withdrawn is the total storage paid - storage being used up by the current sales and buy offers
+        let available = balance.saturating_sub(self.internal_storage_used(&owner_id));
+        let amount = amount.map(|a| a.0).unwrap_or(available);
+        assert!(amount <= available, "Cannot withdraw more than the available storage balance: {}", available);
+
+        //if that excess to withdraw is > 0, we transfer the amount to the user.
+        if amount > 0 {
+            Promise::new(owner_id.clone()).transfer(amount);
+        }
+        //the storage being used up stays in the map so that if those sales get taken down, the user can then withdraw it
+        self.storage_deposits.insert(&owner_id, &(balance - amount));
+        self.internal_storage_balance(&owner_id, balance - amount)
+    }
+
+    //Allows users to remove their account from the marketplace and get their whole storage balance back. Returns false if the
+    //account wasn't registered. If the account still has listings or buy offers, it panics unless force is true in which case
+    //the sales, bundles and lazy mint listings are taken down and the buy offers are cancelled and refunded.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        assert_one_yocto();
+
+        let owner_id = env::predecessor_account_id();
+        let balance = if let Some(balance) = self.storage_deposits.get(&owner_id) {
+            balance
+        } else {
+            return false;
+        };
+
+        if self.internal_storage_used(&owner_id) > 0 {
+            assert!(force.unwrap_or(false), "Can't unregister the account with active listings or buy offers without force");
+
+            //take down all the sales of the account (refunding any bidders)
+            let sales = self.by_owner_id.get(&owner_id).map(|s| s.to_vec()).unwrap_or_default();
+            for contract_and_token_id in sales {
+                let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+                self.internal_cancel_sale(sale.nft_contract_id.parse().unwrap(), sale.token_id, "unregistered");
+            }
+
+            //take down all the bundles of the account
+            let bundle_ids = self.bundles_by_owner_id.get(&owner_id).map(|s| s.to_vec()).unwrap_or_default();
+            for bundle_id in bundle_ids {
+                self.internal_remove_bundle(bundle_id);
+            }
+
+            //take down all the lazy mint listings of the account
+            let listing_ids = self.lazy_mints_by_owner_id.get(&owner_id).map(|s| s.to_vec()).unwrap_or_default();
+            for listing_id in listing_ids {
+                let listing = self.lazy_mints.get(&listing_id).expect("No lazy mint listing");
+                self.internal_remove_lazy_mint(&listing.nft_contract_id, listing.series_id);
+            }
+
+            //cancel all the buy offers of the account and refund the escrowed amounts
+            let offer_ids = self.buy_offers_by_buyer_id.get(&owner_id).map(|s| s.to_vec()).unwrap_or_default();
+            for offer_id in offer_ids {
+                let offer = self.internal_remove_buy_offer(offer_id);
+                Promise::new(offer.buyer_id).transfer(offer.amount.0);
+            }
+        }
+
+        //remove the account and refund its whole storage balance
+        self.storage_deposits.remove(&owner_id);
+        if balance > 0 {
+            Promise::new(owner_id).transfer(balance);
+        }
+        true
+    }
+
+    // views
+    // return the minimum storage for 1 sale. There is no maximum since every sale or buy offer needs more storage
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(STORAGE_PER_SALE),
+            max: None,
+        }
+    }
+
+    // return how much storage an account has paid for and how much of it can be withdrawn. If it isn't registered, return None
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get(&account_id)
+            .map(|balance| self.internal_storage_balance(&account_id, balance))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // return the minimum storage for 1 sale. Kept for frontends that don't use storage_balance_bounds yet
+    pub fn storage_minimum_balance(&self) -> U128 {
+        U128(STORAGE_PER_SALE)
+    }
+}
+
+impl Contract {
+    //internal method for building the storage balance of an account from its total deposit
+    fn internal_storage_balance(&self, account_id: &AccountId, total: Balance) -> StorageBalance {
+        StorageBalance {
+            total: U128(total),
+            available: U128(total.saturating_sub(self.internal_storage_used(account_id))),
+        }
+    }
+}
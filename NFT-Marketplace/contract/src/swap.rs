@@ -0,0 +1,272 @@
+use crate::*;
+use near_sdk::is_promise_success;
+
+//struct for the token a swap listing asks for in exchange for the listed token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapTerms {
+    pub nft_contract_id: AccountId,                 //nft contract the requested token was minted on
+    pub token_id: Option<TokenId>,                  //requested token. If None, any token from the nft contract is accepted
+}
+
+// struct for the args passed in the `msg` of nft_approve when a counterparty accepts a swap listing with their token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptSwapArgs {
+    pub accept_swap: SaleKey,
+}
+
+//cross contract call to an nft contract. This is used to move both tokens of a swap
+#[ext_contract(ext_nft_transfer)]
+trait ExtNftTransfer {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId, //account to transfer the token to
+        token_id: TokenId, //token ID to transfer
+        approval_id: Option<u64>, //approval ID in order to transfer the token on behalf of the owner
+        memo: Option<String>, //memo (to include some context)
+    );
+}
+
+//used to transfer a token on behalf of its owner (or the market itself while it holds a token in escrow)
+fn transfer_nft(nft_contract_id: AccountId, receiver_id: AccountId, token_id: TokenId, approval_id: Option<u64>) -> Promise {
+    ext_nft_transfer::ext(nft_contract_id)
+        .with_attached_deposit(1)
+        .with_static_gas(GAS_FOR_NFT_TRANSFER)
+        .nft_transfer(receiver_id, token_id, approval_id, Some("swap from market".to_string()))
+}
+
+//used to send a token out of the market's escrow. If the transfer fails, the token is kept claimable by the receiver
+fn release_escrowed_nft(nft_contract_id: AccountId, receiver_id: AccountId, token_id: TokenId) -> Promise {
+    transfer_nft(nft_contract_id.clone(), receiver_id.clone(), token_id.clone(), None)
+    .then(
+        Contract::ext(env::current_account_id())
+        .with_static_gas(GAS_FOR_RESOLVE_ESCROW_RELEASE)
+        .resolve_escrow_release(nft_contract_id, token_id, receiver_id)
+    )
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        private method used to resolve the promise when the counterparty's token is moved into the market's escrow.
+        If the listed token is still up for this swap, it's transferred to the counterparty. Otherwise the escrowed token is returned.
+    */
+    #[private]
+    pub fn resolve_swap_escrow(
+        &mut self,
+        sale_key: SaleKey,
+        approval_id: u64,
+        counterparty_id: AccountId,
+        swap_nft_contract_id: AccountId,
+        swap_token_id: TokenId,
+    ) -> PromiseOrValue<bool> {
+        //if the counterparty's token couldn't be escrowed, nothing was transferred
+        if !is_promise_success() {
+            let owner_id = self.sales.get(&format!("{}{}{}", sale_key.nft_contract_id, DELIMITER, sale_key.token_id)).map(|sale| sale.owner_id);
+            self.internal_log_swap_failed(owner_id.as_ref(), &sale_key, &counterparty_id, &swap_nft_contract_id, &swap_token_id, "escrow_failed");
+            return PromiseOrValue::Value(false);
+        }
+
+        //make sure the sale is still the one that was accepted. It could have been purchased or re-listed in the meantime
+        let contract_and_token_id = format!("{}{}{}", sale_key.nft_contract_id, DELIMITER, sale_key.token_id);
+        let sale = self.sales.get(&contract_and_token_id);
+        let is_available = sale.as_ref().is_some_and(|sale| {
+            sale.approval_id == approval_id && sale.swap_for.as_ref().is_some_and(|terms| terms.accepts(&swap_nft_contract_id, &swap_token_id))
+        });
+        if !is_available {
+            self.internal_log_swap_failed(sale.map(|sale| sale.owner_id).as_ref(), &sale_key, &counterparty_id, &swap_nft_contract_id, &swap_token_id, "sale_unavailable");
+            release_escrowed_nft(swap_nft_contract_id, counterparty_id, swap_token_id);
+            return PromiseOrValue::Value(false);
+        }
+
+        //take the sale down and transfer the listed token to the counterparty using the market's approval
+        let sale = self.internal_remove_sale(sale_key.nft_contract_id.clone(), sale_key.token_id.clone());
+        transfer_nft(sale_key.nft_contract_id.clone(), counterparty_id.clone(), sale_key.token_id.clone(), Some(sale.approval_id))
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_SWAP)
+            .resolve_swap(sale_key, sale.owner_id, counterparty_id, swap_nft_contract_id, swap_token_id)
+        )
+        .into()
+    }
+
+    /*
+        private method used to resolve the promise when the listed token is transferred to the counterparty. If it went through,
+        the escrowed token is sent to the seller. If it didn't, the escrowed token is returned to the counterparty.
+    */
+    #[private]
+    pub fn resolve_swap(
+        &mut self,
+        sale_key: SaleKey,
+        owner_id: AccountId,
+        counterparty_id: AccountId,
+        swap_nft_contract_id: AccountId,
+        swap_token_id: TokenId,
+    ) -> bool {
+        if !is_promise_success() {
+            //revert the first transfer by returning the escrowed token to the counterparty
+            self.internal_log_swap_failed(Some(&owner_id), &sale_key, &counterparty_id, &swap_nft_contract_id, &swap_token_id, "transfer_failed");
+            release_escrowed_nft(swap_nft_contract_id, counterparty_id, swap_token_id);
+            return false;
+        }
+
+        //send the escrowed token to the seller
+        release_escrowed_nft(swap_nft_contract_id.clone(), owner_id.clone(), swap_token_id.clone());
+
+        // Construct the swap log as per the events standard.
+        let sale_swap_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SaleSwap(vec![SaleSwapLog {
+                owner_id: Some(owner_id.to_string()),
+                nft_contract_id: sale_key.nft_contract_id.to_string(),
+                token_id: sale_key.token_id,
+                counterparty_id: counterparty_id.to_string(),
+                swap_nft_contract_id: swap_nft_contract_id.to_string(),
+                swap_token_id,
+                reason: None,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&sale_swap_log.to_string());
+        true
+    }
+
+    /*
+        private method used to resolve the promise when an escrowed token is sent out of the market. If the transfer failed,
+        the token is recorded so the account it was meant for can claim it later.
+    */
+    #[private]
+    pub fn resolve_escrow_release(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+    ) -> bool {
+        if is_promise_success() {
+            return true;
+        }
+
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        self.escrowed_tokens.insert(&contract_and_token_id, &owner_id);
+
+        // Construct the escrow log as per the events standard.
+        let escrow_claimable_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::EscrowClaimable(vec![EscrowClaimableLog {
+                owner_id: owner_id.to_string(),
+                nft_contract_id: nft_contract_id.to_string(),
+                token_id,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&escrow_claimable_log.to_string());
+        false
+    }
+
+    //retries sending a token the market still holds in escrow to the account it belongs to. If it fails again, the token stays claimable
+    #[payable]
+    pub fn claim_escrowed_token(&mut self, nft_contract_id: AccountId, token_id: TokenId) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //only the account the token was meant for can claim it
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMITER, token_id);
+        let owner_id = self.escrowed_tokens.get(&contract_and_token_id).expect("No escrowed token");
+        assert_eq!(env::predecessor_account_id(), owner_id, "Only the owner of the escrowed token can claim it");
+
+        self.escrowed_tokens.remove(&contract_and_token_id);
+        release_escrowed_nft(nft_contract_id, owner_id, token_id)
+    }
+
+    // views
+    //returns the account that can claim a token still held in escrow (if there is one)
+    pub fn get_escrowed_token_owner(&self, nft_contract_token: ContractAndTokenId) -> Option<AccountId> {
+        self.escrowed_tokens.get(&nft_contract_token)
+    }
+}
+
+impl SwapTerms {
+    //returns true if the given token can be swapped for the listed token
+    pub(crate) fn accepts(&self, nft_contract_id: &AccountId, token_id: &TokenId) -> bool {
+        &self.nft_contract_id == nft_contract_id && self.token_id.as_ref().is_none_or(|id| id == token_id)
+    }
+}
+
+impl Contract {
+    /*
+        internal method called from nft_on_approve when a counterparty accepts a swap listing with one of their tokens. The token
+        is first moved into the market's escrow using the approval that was just given so the swap can be reverted if the
+        listed token can't be transferred.
+    */
+    pub(crate) fn internal_accept_swap(
+        &mut self,
+        swap_nft_contract_id: AccountId,
+        swap_token_id: TokenId,
+        counterparty_id: AccountId,
+        swap_approval_id: u64,
+        sale_key: SaleKey,
+    ) -> Promise {
+        //get the swap listing. If it doesn't exist, panic
+        let contract_and_token_id = format!("{}{}{}", sale_key.nft_contract_id, DELIMITER, sale_key.token_id);
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //make sure the token offered is one that the listing asks for and that the counterparty can take the listed token
+        let terms = sale.swap_for.as_ref().expect("Sale is not a swap listing");
+        assert!(terms.accepts(&swap_nft_contract_id, &swap_token_id), "Token is not accepted by the swap listing");
+        assert_ne!(sale.owner_id, counterparty_id, "Cannot accept your own swap listing");
+        sale.assert_purchase_window();
+        assert!(sale.is_reserved_for(&counterparty_id), "Sale is reserved for other accounts");
+
+        //move the counterparty's token into the market's escrow, then transfer the listed token
+        transfer_nft(swap_nft_contract_id.clone(), env::current_account_id(), swap_token_id.clone(), Some(swap_approval_id))
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_SWAP_ESCROW)
+            .resolve_swap_escrow(sale_key, sale.approval_id, counterparty_id, swap_nft_contract_id, swap_token_id)
+        )
+    }
+
+    //internal method for logging a swap that didn't go through
+    fn internal_log_swap_failed(
+        &self,
+        owner_id: Option<&AccountId>,
+        sale_key: &SaleKey,
+        counterparty_id: &AccountId,
+        swap_nft_contract_id: &AccountId,
+        swap_token_id: &TokenId,
+        reason: &str,
+    ) {
+        // Construct the failed swap log as per the events standard.
+        let sale_swap_failed_log: EventLog = EventLog {
+            // Standard name ("nft_market").
+            standard: MARKET_STANDARD_NAME.to_string(),
+            // Version of the standard ("1.0.0").
+            version: MARKET_EVENTS_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::SaleSwapFailed(vec![SaleSwapLog {
+                owner_id: owner_id.map(|owner_id| owner_id.to_string()),
+                nft_contract_id: sale_key.nft_contract_id.to_string(),
+                token_id: sale_key.token_id.clone(),
+                counterparty_id: counterparty_id.to_string(),
+                swap_nft_contract_id: swap_nft_contract_id.to_string(),
+                swap_token_id: swap_token_id.clone(),
+                // Why the swap didn't go through.
+                reason: Some(reason.to_string()),
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&sale_swap_failed_log.to_string());
+    }
+}
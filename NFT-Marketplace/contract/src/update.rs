@@ -0,0 +1,22 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /*
+        Allows the owner to update the marketplace code. The new code is passed in as the raw input to avoid the gas overhead
+        of deserializing it as a parameter. Once it's deployed, migrate is called to bring the state to the new layout.
+    */
+    pub fn update_contract(&self) -> Promise {
+        //make sure only the owner can update the code
+        self.assert_owner();
+
+        //receive the code directly from the input
+        let code = env::input().expect("Error: No input").to_vec();
+
+        //deploy the code on the marketplace and migrate the state in the same receipt
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_owned(), NO_ARGS, 0, GAS_FOR_MIGRATE)
+            .as_return()
+    }
+}
@@ -0,0 +1,322 @@
+use near_units::parse_near;
+use serde_json::{json, Value};
+use std::{env, fs};
+use workspaces::{Account, Contract};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // the marketplace, NFT-Contract and NFT-Custom wasm files are passed in that order
+    let mut wasms = vec![];
+    for wasm_arg in env::args().skip(1).take(3) {
+        let wasm_filepath = fs::canonicalize(env::current_dir()?.join(wasm_arg))?;
+        wasms.push(std::fs::read(wasm_filepath)?);
+    }
+
+    let worker = workspaces::sandbox().await?;
+    let market = worker.dev_deploy(&wasms[0]).await?;
+    let nft = worker.dev_deploy(&wasms[1]).await?;
+    let custom = worker.dev_deploy(&wasms[2]).await?;
+
+    // create accounts
+    let account = worker.dev_create_account().await?;
+    let alice = account
+        .create_subaccount("alice")
+        .initial_balance(parse_near!("30 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    let bob = account
+        .create_subaccount("bob")
+        .initial_balance(parse_near!("30 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    let carol = account
+        .create_subaccount("carol")
+        .initial_balance(parse_near!("30 N"))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // initialize the contracts. The marketplace owns itself so it can change its own settings
+    market
+        .call("new")
+        .args_json(json!({"owner_id": market.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+    // alice owns both nft contracts so she can mint on them
+    nft.call("new_default_meta")
+        .args_json(json!({"owner_id": alice.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+    custom
+        .call("new_default_meta")
+        .args_json(json!({"owner_id": alice.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // alice pays for 10 sales on the market and mints two tokens with a 10% royalty for carol
+    alice
+        .call(market.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(parse_near!("0.1 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    for token_id in ["token-1", "token-2"] {
+        alice
+            .call(nft.id(), "nft_mint")
+            .args_json(json!({
+                "token_id": token_id,
+                "metadata": {"title": token_id},
+                "receiver_id": alice.id(),
+                "perpetual_royalties": {carol.id().to_string(): 1000}
+            }))
+            .deposit(parse_near!("0.1 N"))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    // begin tests
+    test_list_token(&alice, &market, &nft).await?;
+    test_update_price(&alice, &market, &nft).await?;
+    test_purchase_pays_royalties(&bob, &carol, &market, &nft).await?;
+    test_refund_on_failed_transfer(&alice, &bob, &market, &nft).await?;
+    test_storage_withdraw(&alice, &market).await?;
+    test_malicious_payout_rejected(&alice, &bob, &carol, &market, &custom).await?;
+    test_too_many_payout_receivers_rejected(&alice, &bob, &carol, &market, &custom).await?;
+
+    Ok(())
+}
+
+// approves the market for a token which lists it for the given price in yoctoNEAR
+async fn list_token(seller: &Account, market: &Contract, nft_contract: &Contract, token_id: &str, price: u128) -> anyhow::Result<()> {
+    seller
+        .call(nft_contract.id(), "nft_approve")
+        .args_json(json!({
+            "token_id": token_id,
+            "account_id": market.id(),
+            "msg": json!({"sale_conditions": price.to_string()}).to_string()
+        }))
+        .deposit(parse_near!("0.01 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+async fn get_sale(user: &Account, market: &Contract, nft_contract: &Contract, token_id: &str) -> anyhow::Result<Value> {
+    let sale: Value = user
+        .call(market.id(), "get_sale")
+        .args_json(json!({"nft_contract_token": format!("{}.{}", nft_contract.id(), token_id)}))
+        .transact()
+        .await?
+        .json()?;
+    Ok(sale)
+}
+
+async fn get_token_owner(user: &Account, nft_contract: &Contract, token_id: &str) -> anyhow::Result<String> {
+    let token: Value = user
+        .call(nft_contract.id(), "nft_token")
+        .args_json(json!({"token_id": token_id}))
+        .transact()
+        .await?
+        .json()?;
+    Ok(token["owner_id"].as_str().unwrap().to_string())
+}
+
+async fn test_list_token(seller: &Account, market: &Contract, nft_contract: &Contract) -> anyhow::Result<()> {
+    list_token(seller, market, nft_contract, "token-1", parse_near!("1 N")).await?;
+
+    let sale = get_sale(seller, market, nft_contract, "token-1").await?;
+    assert_eq!(sale["owner_id"], seller.id().to_string());
+    assert_eq!(sale["sale_conditions"], parse_near!("1 N").to_string());
+    println!("      Passed ✅ lists a token");
+    Ok(())
+}
+
+async fn test_update_price(seller: &Account, market: &Contract, nft_contract: &Contract) -> anyhow::Result<()> {
+    seller
+        .call(market.id(), "update_price")
+        .args_json(json!({"nft_contract_id": nft_contract.id(), "token_id": "token-1", "price": parse_near!("2 N").to_string()}))
+        .deposit(1)
+        .transact()
+        .await?
+        .into_result()?;
+
+    let sale = get_sale(seller, market, nft_contract, "token-1").await?;
+    assert_eq!(sale["sale_conditions"], parse_near!("2 N").to_string());
+    println!("      Passed ✅ updates the price of a sale");
+    Ok(())
+}
+
+async fn test_purchase_pays_royalties(buyer: &Account, royalty_account: &Account, market: &Contract, nft_contract: &Contract) -> anyhow::Result<()> {
+    let royalty_balance = royalty_account.view_account().await?.balance;
+
+    buyer
+        .call(market.id(), "offer")
+        .args_json(json!({"nft_contract_id": nft_contract.id(), "token_id": "token-1"}))
+        .deposit(parse_near!("2 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    // the token is transferred and 10% of the price goes to the royalty account
+    assert_eq!(get_token_owner(buyer, nft_contract, "token-1").await?, buyer.id().to_string());
+    assert_eq!(royalty_account.view_account().await?.balance - royalty_balance, parse_near!("0.2 N"));
+    assert!(get_sale(buyer, market, nft_contract, "token-1").await?.is_null());
+    println!("      Passed ✅ purchases a token and pays royalties");
+    Ok(())
+}
+
+async fn test_refund_on_failed_transfer(seller: &Account, buyer: &Account, market: &Contract, nft_contract: &Contract) -> anyhow::Result<()> {
+    list_token(seller, market, nft_contract, "token-2", parse_near!("1 N")).await?;
+
+    // the seller revokes the market on the nft contract so the sale can no longer be transferred
+    seller
+        .call(nft_contract.id(), "nft_revoke")
+        .args_json(json!({"token_id": "token-2", "account_id": market.id()}))
+        .deposit(1)
+        .transact()
+        .await?
+        .into_result()?;
+
+    let buyer_balance = buyer.view_account().await?.balance;
+    buyer
+        .call(market.id(), "offer")
+        .args_json(json!({"nft_contract_id": nft_contract.id(), "token_id": "token-2"}))
+        .deposit(parse_near!("1 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    // the buyer only paid for gas and the seller still owns the token
+    assert!(buyer_balance - buyer.view_account().await?.balance < parse_near!("0.1 N"));
+    assert_eq!(get_token_owner(buyer, nft_contract, "token-2").await?, seller.id().to_string());
+    println!("      Passed ✅ refunds the buyer when the transfer fails");
+    Ok(())
+}
+
+async fn test_storage_withdraw(seller: &Account, market: &Contract) -> anyhow::Result<()> {
+    // both sales are gone so all of the storage can be withdrawn
+    let balance: Value = seller
+        .call(market.id(), "storage_balance_of")
+        .args_json(json!({"account_id": seller.id()}))
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(balance["available"], parse_near!("0.1 N").to_string());
+
+    let balance: Value = seller
+        .call(market.id(), "storage_withdraw")
+        .args_json(json!({"amount": parse_near!("0.05 N").to_string()}))
+        .deposit(1)
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(balance["total"], parse_near!("0.05 N").to_string());
+    println!("      Passed ✅ withdraws unused storage");
+    Ok(())
+}
+
+async fn test_malicious_payout_rejected(
+    seller: &Account,
+    buyer: &Account,
+    royalty_account: &Account,
+    market: &Contract,
+    custom: &Contract,
+) -> anyhow::Result<()> {
+    // the market only accepts payouts that give at most 10% to royalties
+    market
+        .call("set_payout_policy")
+        .args_json(json!({"max_royalty_basis_points": 1000, "min_seller_basis_points": 0}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // the series asks for a 50% royalty on every sale
+    seller
+        .call(custom.id(), "create_series")
+        .args_json(json!({"id": 1, "metadata": {"title": "series"}, "royalty": {royalty_account.id().to_string(): 5000}}))
+        .deposit(parse_near!("0.1 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    seller
+        .call(custom.id(), "nft_mint")
+        .args_json(json!({"id": "1", "receiver_id": seller.id()}))
+        .deposit(parse_near!("0.1 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    list_token(seller, market, custom, "1:1", parse_near!("1 N")).await?;
+
+    let buyer_balance = buyer.view_account().await?.balance;
+    let royalty_balance = royalty_account.view_account().await?.balance;
+    let outcome = buyer
+        .call(market.id(), "offer")
+        .args_json(json!({"nft_contract_id": custom.id(), "token_id": "1:1"}))
+        .deposit(parse_near!("1 N"))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(outcome.logs().iter().any(|log| log.contains("royalty_above_maximum")));
+
+    // the token is never transferred, the buyer is refunded and the royalty account isn't paid
+    assert_eq!(get_token_owner(buyer, custom, "1:1").await?, seller.id().to_string());
+    assert!(buyer_balance - buyer.view_account().await?.balance < parse_near!("0.1 N"));
+    assert_eq!(royalty_account.view_account().await?.balance, royalty_balance);
+    println!("      Passed ✅ rejects a payout that breaks the royalty policy");
+    Ok(())
+}
+
+async fn test_too_many_payout_receivers_rejected(
+    seller: &Account,
+    buyer: &Account,
+    royalty_account: &Account,
+    market: &Contract,
+    custom: &Contract,
+) -> anyhow::Result<()> {
+    // the series pays a tiny royalty to more accounts than the market can pay out at once
+    let royalty: serde_json::Map<String, Value> = (0..11)
+        .map(|i| (format!("royalty{}.{}", i, royalty_account.id()), json!(1)))
+        .collect();
+    seller
+        .call(custom.id(), "create_series")
+        .args_json(json!({"id": 2, "metadata": {"title": "crowded series"}, "royalty": royalty}))
+        .deposit(parse_near!("0.1 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    seller
+        .call(custom.id(), "nft_mint")
+        .args_json(json!({"id": "2", "receiver_id": seller.id()}))
+        .deposit(parse_near!("0.1 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    list_token(seller, market, custom, "2:1", parse_near!("1 N")).await?;
+
+    let buyer_balance = buyer.view_account().await?.balance;
+    let outcome = buyer
+        .call(market.id(), "offer")
+        .args_json(json!({"nft_contract_id": custom.id(), "token_id": "2:1"}))
+        .deposit(parse_near!("1 N"))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(outcome.logs().iter().any(|log| log.contains("invalid_payout")));
+
+    // the token stays with the seller and the buyer is refunded
+    assert_eq!(get_token_owner(buyer, custom, "2:1").await?, seller.id().to_string());
+    assert!(buyer_balance - buyer.view_account().await?.balance < parse_near!("0.1 N"));
+    println!("      Passed ✅ rejects a payout with too many receivers");
+    Ok(())
+}